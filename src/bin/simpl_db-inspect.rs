@@ -0,0 +1,73 @@
+use simpl_db::inspect::{self, PageFile};
+
+// Usage: simpl_db-inspect [--page-size <bytes>] <db_file> hexdump|header|cells <page_num>
+//        simpl_db-inspect [--page-size <bytes>] <db_file> histogram
+// --page-size reads a file whose header is damaged, without it the size is guessed.
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let page_size = parse_page_size(&mut args);
+    if args.len() < 2 {
+        eprintln!("Usage: simpl_db-inspect [--page-size <bytes>] <db_file> hexdump|header|cells <page_num>");
+        eprintln!("       simpl_db-inspect [--page-size <bytes>] <db_file> histogram");
+        std::process::exit(1);
+    }
+    let db_file_path = &args[0];
+    let mut page_file = PageFile::open(db_file_path, page_size).unwrap_or_else(|err| {
+        eprintln!("Error opening '{}': {}", db_file_path, err);
+        if page_size.is_none() {
+            eprintln!("Pass --page-size to read it anyway.");
+        }
+        std::process::exit(1);
+    });
+    if let Some(err) = &page_file.header_error {
+        eprintln!("Warning: {}, reading pages of {} bytes.", err, page_file.page_size);
+    }
+    let output = match args[1].as_str() {
+        "histogram" => inspect::page_type_histogram(&mut page_file),
+        command @ ("hexdump" | "header" | "cells") => {
+            let page_num = parse_page_num(command, args.get(2));
+            page_file.read_page(page_num).map(|page| match command {
+                "hexdump" => inspect::hexdump(&page),
+                "header" => inspect::describe_page(page_num, &page),
+                _ => inspect::describe_cells(page_num, &page),
+            })
+        }
+        command => {
+            eprintln!("Unrecognized command '{}', expected hexdump, header, cells or histogram.", command);
+            std::process::exit(1);
+        }
+    };
+    match output {
+        Ok(output) => print!("{}", output),
+        Err(err) => {
+            eprintln!("Error reading '{}': {}", db_file_path, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Takes `--page-size <bytes>` out of the arguments, wherever it appears.
+fn parse_page_size(args: &mut Vec<String>) -> Option<usize> {
+    let index = args.iter().position(|arg| arg == "--page-size")?;
+    let value = args.get(index + 1).map(|value| value.parse::<usize>());
+    match value {
+        Some(Ok(page_size)) => {
+            args.drain(index..index + 2);
+            Some(page_size)
+        }
+        _ => {
+            eprintln!("Expected a number after '--page-size'.");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn parse_page_num(command: &str, value: Option<&String>) -> usize {
+    match value.map(|value| value.parse::<usize>()) {
+        Some(Ok(page_num)) => page_num,
+        _ => {
+            eprintln!("Expected a page number after '{}'.", command);
+            std::process::exit(1);
+        }
+    }
+}
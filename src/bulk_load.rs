@@ -0,0 +1,192 @@
+use crate::constants::*;
+use crate::enums::DbError;
+use crate::node::{
+    initialize_internal_node, initialize_leaf_node, set_next_leaf, set_node_parent, set_node_root, set_prev_leaf,
+    write_internal_node, write_leaf_node_cells,
+};
+use crate::overflow::leaf_cell;
+use crate::row::Row;
+use crate::table::Table;
+
+/// Loads rows sorted by id into an empty table, building the tree bottom up
+/// instead of inserting them one at a time. Leaves are filled to `fill_percent`
+/// of their capacity. Outside a transaction the load is committed like a
+/// statement. Returns the number of rows loaded.
+pub fn bulk_load(table: &mut Table, rows: &[Row], fill_percent: usize) -> Result<usize, DbError> {
+    check_fill_percent(fill_percent)?;
+    if !table.table_start()?.end_of_table {
+        return Err(DbError::InvalidArgument("bulk loads need an empty table".to_string()));
+    }
+    // checked before anything is written so a rejected load leaves the table as it was
+    for pair in rows.windows(2) {
+        if pair[0].id >= pair[1].id {
+            return Err(DbError::InvalidArgument(format!(
+                "id {} follows {}, rows must be sorted by id without duplicates",
+                pair[1].id, pair[0].id
+            )));
+        }
+    }
+    if let Some(row) = rows.iter().find(|row| row.username.len().max(row.email.len()) > MAX_FIELD_SIZE) {
+        return Err(DbError::InvalidArgument(format!(
+            "row {} has a field longer than {} bytes",
+            row.id, MAX_FIELD_SIZE
+        )));
+    }
+    table.run_statement(|table| {
+        let cells = rows
+            .iter()
+            .map(|row| leaf_cell(&mut table.pager, &row.serialize_row()))
+            .collect::<Result<Vec<Vec<u8>>, DbError>>()?;
+        build_tree(table, &cells, fill_percent)
+    })?;
+    Ok(rows.len())
+}
+
+/// Reads rows for a bulk load, one `<id> <username> <email>` per line as in an insert.
+pub fn read_rows(file_name: &str) -> Result<Vec<Row>, DbError> {
+    let contents = std::fs::read_to_string(file_name)?;
+    let mut rows = Vec::new();
+    for (line_num, line) in contents.lines().enumerate() {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let row = match parts.as_slice() {
+            [] => continue,
+            [id_str, username, email] => match id_str.parse::<i32>() {
+                Ok(id) if id >= 0 => Row {
+                    id,
+                    username: username.to_string(),
+                    email: email.to_string(),
+                },
+                _ => {
+                    return Err(DbError::InvalidArgument(format!(
+                        "line {}: '{}' is not a positive id",
+                        line_num + 1,
+                        id_str
+                    )))
+                }
+            },
+            _ => {
+                return Err(DbError::InvalidArgument(format!(
+                    "line {}: expected '<id> <username> <email>'",
+                    line_num + 1
+                )))
+            }
+        };
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+pub fn check_fill_percent(fill_percent: usize) -> Result<(), DbError> {
+    if !(MIN_FILL_PERCENT..=100).contains(&fill_percent) {
+        return Err(DbError::InvalidArgument(format!(
+            "fill factor {} is not between {} and 100",
+            fill_percent, MIN_FILL_PERCENT
+        )));
+    }
+    Ok(())
+}
+
+/// Builds the tree bottom up from cells in key order: the leaves first, filled
+/// to `fill_percent` of the space for cells and chained both ways, then one
+/// level of internal nodes at a time until a level fits in the root page. The
+/// root must be an empty leaf.
+pub fn build_tree(table: &mut Table, cells: &[Vec<u8>], fill_percent: usize) -> Result<(), DbError> {
+    let page_size = table.pager.page_size;
+    let leaf_capacity = leaf_node_space_for_cells(page_size) * fill_percent / 100;
+    let leaves = pack_leaves(cells, leaf_capacity);
+    if leaves.len() <= 1 {
+        write_leaf_node_cells(table.pager.get_page(table.root_page_num)?, cells);
+        return Ok(());
+    }
+
+    let leaf_page_nums = leaves
+        .iter()
+        .map(|_| table.pager.allocate_page())
+        .collect::<Result<Vec<usize>, DbError>>()?;
+    // page number and max key of each node on the level being built
+    let mut level: Vec<(usize, u32)> = Vec::with_capacity(leaves.len());
+    for (i, leaf_cells) in leaves.iter().enumerate() {
+        let next_leaf = leaf_page_nums.get(i + 1).copied().unwrap_or(0);
+        let prev_leaf = if i == 0 { 0 } else { leaf_page_nums[i - 1] };
+        let node = table.pager.get_page(leaf_page_nums[i])?;
+        initialize_leaf_node(node);
+        write_leaf_node_cells(node, leaf_cells);
+        set_next_leaf(node, next_leaf as u32);
+        set_prev_leaf(node, prev_leaf as u32);
+        level.push((leaf_page_nums[i], cell_key(&leaf_cells[leaf_cells.len() - 1])));
+    }
+
+    loop {
+        let nodes = split_evenly(&level, internal_node_max_cells(page_size) + 1);
+        let is_root = nodes.len() == 1;
+        let mut parent_level = Vec::with_capacity(nodes.len());
+        for entries in nodes {
+            let page_num = if is_root { table.root_page_num } else { table.pager.allocate_page()? };
+            let children: Vec<usize> = entries.iter().map(|&(child, _)| child).collect();
+            let keys: Vec<u32> = entries[..entries.len() - 1].iter().map(|&(_, key)| key).collect();
+            let node = table.pager.get_page(page_num)?;
+            initialize_internal_node(node);
+            write_internal_node(node, &children, &keys);
+            set_node_root(node, is_root);
+            for &child in &children {
+                set_node_parent(table.pager.get_page(child)?, page_num as u32);
+            }
+            parent_level.push((page_num, entries[entries.len() - 1].1));
+        }
+        if is_root {
+            return Ok(());
+        }
+        level = parent_level;
+    }
+}
+
+// Splits `items` into as few runs of at most `capacity` as possible, with run
+// lengths differing by at most one so no node ends up nearly empty.
+fn split_evenly<T>(items: &[T], capacity: usize) -> Vec<&[T]> {
+    let count = items.len().div_ceil(capacity);
+    let mut runs = Vec::with_capacity(count);
+    let mut start = 0;
+    for i in 0..count {
+        let len = items.len() / count + usize::from(i < items.len() % count);
+        runs.push(&items[start..start + len]);
+        start += len;
+    }
+    runs
+}
+
+// Fills leaves in order up to `capacity` bytes, slots included, with at least
+// one cell each. When that leaves the last leaf under half full, the last two
+// share their cells evenly instead.
+fn pack_leaves(cells: &[Vec<u8>], capacity: usize) -> Vec<&[Vec<u8>]> {
+    if cells.is_empty() {
+        return Vec::new();
+    }
+    let footprints: Vec<usize> = cells.iter().map(|cell| cell.len() + LEAF_NODE_SLOT_SIZE).collect();
+    let mut starts = vec![0];
+    let mut used = 0;
+    for (i, &footprint) in footprints.iter().enumerate() {
+        if used + footprint > capacity && i > starts[starts.len() - 1] {
+            starts.push(i);
+            used = 0;
+        }
+        used += footprint;
+    }
+    if starts.len() > 1 && used * 2 < capacity {
+        let pair_start = starts[starts.len() - 2];
+        let total: usize = footprints[pair_start..].iter().sum();
+        let mut left = 0;
+        let mut split = pair_start;
+        while left * 2 < total {
+            left += footprints[split];
+            split += 1;
+        }
+        let last = starts.len() - 1;
+        starts[last] = split;
+    }
+    starts.push(cells.len());
+    starts.windows(2).map(|run| &cells[run[0]..run[1]]).collect()
+}
+
+fn cell_key(cell: &[u8]) -> u32 {
+    u32::from_le_bytes(cell[LEAF_NODE_KEY_OFFSET..LEAF_NODE_KEY_OFFSET + LEAF_NODE_KEY_SIZE].try_into().unwrap())
+}
@@ -0,0 +1,352 @@
+use std::collections::HashSet;
+use std::fmt;
+use crate::constants::*;
+use crate::header::free_list_head;
+use crate::node::{
+    get_node_parent, internal_node_children, internal_node_keys, internal_node_num_keys, leaf_node_cell,
+    leaf_node_cell_content_start, leaf_node_cell_offset, leaf_node_fragmented_bytes, leaf_node_key,
+    leaf_node_next_leaf, leaf_node_num_cells, leaf_node_prev_leaf,
+};
+use crate::overflow::{cell_overflow_page, cell_payload_size, cell_size_within, overflow_page_next};
+use crate::table::Table;
+
+// raw node type bytes, read directly since the node accessors panic on anything else
+const INTERNAL_NODE_TYPE: u8 = 0;
+const LEAF_NODE_TYPE: u8 = 1;
+
+/// One broken invariant found by `check_table`.
+#[derive(Debug)]
+pub struct Violation {
+    pub page_num: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "page {}: {}", self.page_num, self.message)
+    }
+}
+
+/// Walks the tree from the root and the free list, returning every violation
+/// found. An empty list means the file is consistent. Pages that fail their
+/// checksum are reported rather than read.
+pub fn check_table(table: &mut Table) -> Vec<Violation> {
+    let mut checker = Checker {
+        table,
+        violations: Vec::new(),
+        seen_pages: HashSet::new(),
+        leaves: Vec::new(),
+        leaf_depth: None,
+    };
+    let root_page_num = checker.table.root_page_num;
+    checker.check_node(root_page_num, None, 1, None, None);
+    checker.check_leaf_chain();
+    checker.check_free_list();
+    checker.check_every_page_used();
+    checker.violations
+}
+
+struct Checker<'a> {
+    table: &'a mut Table,
+    violations: Vec<Violation>,
+    // tree and free list pages seen so far, a page seen twice is shared
+    seen_pages: HashSet<usize>,
+    // leaves in key order as found walking the tree
+    leaves: Vec<usize>,
+    leaf_depth: Option<usize>,
+}
+
+impl Checker<'_> {
+    fn report(&mut self, page_num: usize, message: String) {
+        self.violations.push(Violation { page_num, message });
+    }
+
+    // Reads a page the walk reached, or reports why it cannot be read.
+    fn read_page(&mut self, page_num: usize, referenced_by: &str) -> Option<Vec<u8>> {
+        if page_num == HEADER_PAGE_NUM || page_num >= self.table.pager.num_pages {
+            self.report(page_num, format!("{} points outside the tree pages", referenced_by));
+            return None;
+        }
+        if !self.seen_pages.insert(page_num) {
+            self.report(page_num, format!("{} points at a page that is already in use", referenced_by));
+            return None;
+        }
+        match self.table.pager.get_page_ref(page_num) {
+            Ok(page) => Some(page.to_vec()),
+            Err(err) => {
+                self.report(page_num, err.to_string());
+                None
+            }
+        }
+    }
+
+    // Checks the subtree at `page_num`, whose keys must be above `min_key` and at
+    // most `max_key`. Returns the largest key in the subtree.
+    fn check_node(
+        &mut self,
+        page_num: usize,
+        parent_page_num: Option<usize>,
+        depth: usize,
+        min_key: Option<u32>,
+        max_key: Option<u32>,
+    ) -> Option<u32> {
+        let referenced_by = match parent_page_num {
+            Some(parent_page_num) => format!("page {}", parent_page_num),
+            None => "the header".to_string(),
+        };
+        let node = self.read_page(page_num, &referenced_by)?;
+
+        let is_root = parent_page_num.is_none();
+        match node[IS_ROOT_OFFSET] {
+            0 if is_root => self.report(page_num, "root is not marked as the root".to_string()),
+            1 if !is_root => self.report(page_num, "node below the root is marked as the root".to_string()),
+            0 | 1 => (),
+            value => self.report(page_num, format!("unknown is_root value {}", value)),
+        }
+        if let Some(parent_page_num) = parent_page_num {
+            let parent_pointer = get_node_parent(&node) as usize;
+            if parent_pointer != parent_page_num {
+                self.report(
+                    page_num,
+                    format!("parent pointer is {} but the node is a child of {}", parent_pointer, parent_page_num),
+                );
+            }
+        }
+
+        let keys = match node[NODE_TYPE_OFFSET] {
+            LEAF_NODE_TYPE => self.check_leaf(page_num, &node, depth)?,
+            INTERNAL_NODE_TYPE => self.check_internal_node(page_num, &node, depth)?,
+            node_type => {
+                self.report(page_num, format!("unknown node type {}", node_type));
+                return None;
+            }
+        };
+        if let (Some(&first_key), Some(min_key)) = (keys.first(), min_key) {
+            if first_key <= min_key {
+                self.report(page_num, format!("key {} is not above {} from the parent", first_key, min_key));
+            }
+        }
+        if let (Some(&last_key), Some(max_key)) = (keys.last(), max_key) {
+            if last_key > max_key {
+                self.report(page_num, format!("key {} is above {} from the parent", last_key, max_key));
+            }
+        }
+        for pair in keys.windows(2) {
+            if pair[0] >= pair[1] {
+                self.report(page_num, format!("keys {} and {} are out of order", pair[0], pair[1]));
+            }
+        }
+        keys.last().copied()
+    }
+
+    // Returns the leaf's keys, or None when the leaf cannot hold them.
+    fn check_leaf(&mut self, page_num: usize, node: &[u8], depth: usize) -> Option<Vec<u32>> {
+        self.leaves.push(page_num);
+        match self.leaf_depth {
+            None => self.leaf_depth = Some(depth),
+            Some(leaf_depth) if leaf_depth != depth => self.report(
+                page_num,
+                format!("leaf is at depth {} but other leaves are at depth {}", depth, leaf_depth),
+            ),
+            Some(_) => (),
+        }
+        let num_cells = leaf_node_num_cells(node) as usize;
+        if num_cells > leaf_node_max_cells(node.len()) {
+            self.report(page_num, format!("leaf has {} cells, more than fit", num_cells));
+            return None;
+        }
+        if num_cells == 0 && depth > 1 {
+            self.report(page_num, "leaf below the root is empty".to_string());
+        }
+        if !self.check_leaf_layout(page_num, node) {
+            return None;
+        }
+        self.check_overflow_chains(page_num, node);
+        Some(
+            (0..num_cells)
+                .map(|cell_num| u32::from_le_bytes(leaf_node_key(node, cell_num).try_into().unwrap()))
+                .collect(),
+        )
+    }
+
+    // Every cell must lie in the cell content area, and the cells plus the
+    // fragmented bytes must account for all of it. Returns false when the cells
+    // cannot be read.
+    fn check_leaf_layout(&mut self, page_num: usize, node: &[u8]) -> bool {
+        let num_cells = leaf_node_num_cells(node) as usize;
+        let slots_end = LEAF_NODE_HEADER_SIZE + num_cells * LEAF_NODE_SLOT_SIZE;
+        let content_end = usable_page_size(node.len());
+        let content_start = leaf_node_cell_content_start(node);
+        if content_start < slots_end || content_start > content_end {
+            self.report(
+                page_num,
+                format!("cell content starts at {}, outside {}..{}", content_start, slots_end, content_end),
+            );
+            return false;
+        }
+        let mut cells_size = 0;
+        for cell_num in 0..num_cells {
+            let cell_offset = leaf_node_cell_offset(node, cell_num);
+            let cell_size = if cell_offset >= content_start && cell_offset < content_end {
+                cell_size_within(&node[cell_offset..content_end], node.len())
+            } else {
+                None
+            };
+            match cell_size {
+                Some(cell_size) => cells_size += cell_size,
+                None => {
+                    self.report(
+                        page_num,
+                        format!("cell {} at offset {} is outside the cell content area", cell_num, cell_offset),
+                    );
+                    return false;
+                }
+            }
+        }
+        let fragmented_bytes = leaf_node_fragmented_bytes(node);
+        if cells_size + fragmented_bytes != content_end - content_start {
+            self.report(
+                page_num,
+                format!(
+                    "cells take {} bytes and {} are fragmented but the cell content area is {} bytes",
+                    cells_size,
+                    fragmented_bytes,
+                    content_end - content_start
+                ),
+            );
+        }
+        true
+    }
+
+    // A spilled cell's chain must be made of overflow pages used nowhere else,
+    // exactly as many as the rest of its payload needs.
+    fn check_overflow_chains(&mut self, page_num: usize, node: &[u8]) {
+        let page_size = node.len();
+        for cell_num in 0..leaf_node_num_cells(node) as usize {
+            let cell = leaf_node_cell(node, cell_num);
+            let mut overflow_page_num = match cell_overflow_page(cell, page_size) {
+                Some(overflow_page_num) => overflow_page_num,
+                None => continue,
+            };
+            let spilled_size = cell_payload_size(cell) - leaf_node_spilled_local_payload(page_size);
+            let expected_pages = spilled_size.div_ceil(overflow_page_capacity(page_size));
+            let mut referenced_by = format!("cell {} of page {}", cell_num, page_num);
+            let mut chain_length = 0;
+            while overflow_page_num != 0 {
+                let page = match self.read_page(overflow_page_num, &referenced_by) {
+                    Some(page) => page,
+                    None => break,
+                };
+                if page[NODE_TYPE_OFFSET] != OVERFLOW_PAGE_TYPE {
+                    self.report(
+                        overflow_page_num,
+                        format!("page in an overflow chain has type {}", page[NODE_TYPE_OFFSET]),
+                    );
+                    break;
+                }
+                chain_length += 1;
+                referenced_by = format!("overflow page {}", overflow_page_num);
+                overflow_page_num = overflow_page_next(&page);
+            }
+            if overflow_page_num == 0 && chain_length != expected_pages {
+                self.report(
+                    page_num,
+                    format!(
+                        "cell {} spills {} bytes to {} overflow pages instead of {}",
+                        cell_num, spilled_size, chain_length, expected_pages
+                    ),
+                );
+            }
+        }
+    }
+
+    // Checks every child and that each key equals its child's max key. Returns
+    // the node's keys followed by the right child's max key, so the caller can
+    // check ordering and bounds the same way as for a leaf.
+    fn check_internal_node(&mut self, page_num: usize, node: &[u8], depth: usize) -> Option<Vec<u32>> {
+        let num_keys = internal_node_num_keys(node) as usize;
+        if num_keys > internal_node_max_cells(node.len()) {
+            self.report(page_num, format!("internal node has {} keys, more than fit", num_keys));
+            return None;
+        }
+        let children = internal_node_children(node);
+        let mut keys = internal_node_keys(node);
+        let mut min_key = None;
+        for (child_num, &child_page_num) in children.iter().enumerate() {
+            // the right child has no key of its own, only the parent's bound
+            let key = keys.get(child_num).copied();
+            let child_max_key = self.check_node(child_page_num, Some(page_num), depth + 1, min_key, key);
+            match (key, child_max_key) {
+                (Some(key), Some(child_max_key)) if key != child_max_key => self.report(
+                    page_num,
+                    format!("key {} does not match the max key {} of child {}", key, child_max_key, child_page_num),
+                ),
+                (None, Some(child_max_key)) => keys.push(child_max_key),
+                _ => (),
+            }
+            min_key = key.or(child_max_key);
+        }
+        Some(keys)
+    }
+
+    // Following `next_leaf` from the first leaf must visit every leaf once, in key
+    // order, and `prev_leaf` from the last leaf the same in reverse.
+    fn check_leaf_chain(&mut self) {
+        let leaves = std::mem::take(&mut self.leaves);
+        for (i, &page_num) in leaves.iter().enumerate() {
+            let (next_leaf, prev_leaf) = match self.table.pager.get_page_ref(page_num) {
+                Ok(page) => (leaf_node_next_leaf(page) as usize, leaf_node_prev_leaf(page) as usize),
+                Err(_) => continue,
+            };
+            let expected = leaves.get(i + 1).copied().unwrap_or(0);
+            if next_leaf != expected {
+                let message = match expected {
+                    0 => format!("last leaf links to page {} instead of ending the chain", next_leaf),
+                    _ => format!("next leaf is {} but the next leaf in key order is {}", next_leaf, expected),
+                };
+                self.report(page_num, message);
+            }
+            let expected = if i == 0 { 0 } else { leaves[i - 1] };
+            if prev_leaf != expected {
+                let message = match expected {
+                    0 => format!("first leaf links back to page {} instead of starting the chain", prev_leaf),
+                    _ => format!("previous leaf is {} but the previous leaf in key order is {}", prev_leaf, expected),
+                };
+                self.report(page_num, message);
+            }
+        }
+    }
+
+    fn check_free_list(&mut self) {
+        let mut page_num = match self.table.pager.get_page_ref(HEADER_PAGE_NUM) {
+            Ok(header) => free_list_head(header),
+            Err(err) => {
+                self.report(HEADER_PAGE_NUM, err.to_string());
+                return;
+            }
+        };
+        let mut referenced_by = "the free list head".to_string();
+        while page_num != 0 {
+            let page = match self.read_page(page_num, &referenced_by) {
+                Some(page) => page,
+                None => return,
+            };
+            if page[NODE_TYPE_OFFSET] != FREE_PAGE_TYPE {
+                self.report(page_num, format!("page on the free list has type {}", page[NODE_TYPE_OFFSET]));
+                return;
+            }
+            referenced_by = format!("free page {}", page_num);
+            page_num = u32::from_le_bytes(
+                page[FREE_PAGE_NEXT_OFFSET..FREE_PAGE_NEXT_OFFSET + FREE_PAGE_NEXT_SIZE].try_into().unwrap(),
+            ) as usize;
+        }
+    }
+
+    fn check_every_page_used(&mut self) {
+        for page_num in HEADER_PAGE_NUM + 1..self.table.pager.num_pages {
+            if !self.seen_pages.contains(&page_num) {
+                self.report(page_num, "page is neither in the tree nor on the free list".to_string());
+            }
+        }
+    }
+}
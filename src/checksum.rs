@@ -0,0 +1,58 @@
+// CRC-32 (IEEE 802.3, the polynomial used by zlib and gzip). Pages are checksummed
+// on every write, so the bytes are consumed eight at a time ("slicing-by-8") from
+// tables built at compile time.
+
+const CRC32_POLYNOMIAL: u32 = 0xEDB8_8320;
+
+// CRC32_TABLES[0] is the classic byte-at-a-time table, CRC32_TABLES[k] advances
+// a byte's contribution past k more zero bytes.
+static CRC32_TABLES: [[u32; 256]; 8] = {
+    let mut tables = [[0; 256]; 8];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ CRC32_POLYNOMIAL } else { crc >> 1 };
+            bit += 1;
+        }
+        tables[0][i] = crc;
+        i += 1;
+    }
+    let mut k = 1;
+    while k < 8 {
+        let mut i = 0;
+        while i < 256 {
+            let previous = tables[k - 1][i];
+            tables[k][i] = (previous >> 8) ^ tables[0][(previous & 0xFF) as usize];
+            i += 1;
+        }
+        k += 1;
+    }
+    tables
+};
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    crc32_update(0, bytes)
+}
+
+/// Continues a checksum over more bytes, `crc32_update(crc32(a), b)` equals `crc32(a ++ b)`.
+pub fn crc32_update(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    let mut chunks = bytes.chunks_exact(8);
+    for chunk in &mut chunks {
+        let low = crc ^ u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        crc = CRC32_TABLES[7][(low & 0xFF) as usize]
+            ^ CRC32_TABLES[6][((low >> 8) & 0xFF) as usize]
+            ^ CRC32_TABLES[5][((low >> 16) & 0xFF) as usize]
+            ^ CRC32_TABLES[4][(low >> 24) as usize]
+            ^ CRC32_TABLES[3][chunk[4] as usize]
+            ^ CRC32_TABLES[2][chunk[5] as usize]
+            ^ CRC32_TABLES[1][chunk[6] as usize]
+            ^ CRC32_TABLES[0][chunk[7] as usize];
+    }
+    for &byte in chunks.remainder() {
+        crc = CRC32_TABLES[0][((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
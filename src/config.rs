@@ -0,0 +1,23 @@
+use crate::constants::{DEFAULT_PAGE_SIZE, DEFAULT_POOL_SIZE};
+use crate::enums::{JournalMode, Synchronous};
+
+#[derive(Clone)]
+pub struct DbConfig {
+    // page size for newly created files, existing files keep the size stored in their header
+    pub page_size: usize,
+    // number of page frames the pager keeps in memory
+    pub pool_size: usize,
+    pub journal_mode: JournalMode,
+    pub synchronous: Synchronous,
+}
+
+impl Default for DbConfig {
+    fn default() -> Self {
+        DbConfig {
+            page_size: DEFAULT_PAGE_SIZE,
+            pool_size: DEFAULT_POOL_SIZE,
+            journal_mode: JournalMode::Wal,
+            synchronous: Synchronous::Full,
+        }
+    }
+}
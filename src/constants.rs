@@ -1,17 +1,37 @@
 use std::mem;
 
+// A serialized row is its id followed by the username and the email, each
+// stored as its length and then its bytes
 pub const ID_SIZE: usize = mem::size_of::<i32>();
-pub const USERNAME_SIZE: usize = mem::size_of::<String>();
-pub const EMAIL_SIZE: usize = mem::size_of::<String>();
-
 pub const ID_OFFSET: usize = 0;
-pub const USERNAME_OFFSET: usize = ID_OFFSET + ID_SIZE;
-pub const EMAIL_OFFSET: usize = USERNAME_OFFSET + USERNAME_SIZE;
-
-pub const ROW_SIZE: usize = ID_SIZE + USERNAME_SIZE + EMAIL_SIZE;
-
-pub const PAGE_SIZE: usize = 128;
-pub const TABLE_MAX_PAGES: usize = 100;
+pub const FIELD_LENGTH_SIZE: usize = mem::size_of::<u16>();
+
+// a row with an empty username and email
+pub const ROW_MIN_SIZE: usize = ID_SIZE + 2 * FIELD_LENGTH_SIZE;
+// the longest username or email a field length can describe
+pub const MAX_FIELD_SIZE: usize = u16::MAX as usize;
+
+pub const DEFAULT_PAGE_SIZE: usize = 4096;
+pub const MIN_PAGE_SIZE: usize = 512;
+pub const MAX_PAGE_SIZE: usize = 64 * 1024;
+pub const DEFAULT_POOL_SIZE: usize = 100;
+// a cursor moving to the next leaf pins it before unpinning the one it leaves,
+// and a split needs a frame for the new page while the cursor's leaf is pinned
+pub const MIN_POOL_SIZE: usize = 2;
+
+// VACUUM and bulk loads pack leaves to this percentage of their capacity unless
+// told otherwise, fuller leaves mean fewer pages but the next inserts split them
+pub const DEFAULT_FILL_PERCENT: usize = 100;
+// below half full a leaf would already count as underflowing
+pub const MIN_FILL_PERCENT: usize = 50;
+
+// Every page ends with a CRC32 of its page number and contents, the rest of the
+// page is left to the node stored in it
+pub const PAGE_CHECKSUM_SIZE: usize = mem::size_of::<u32>();
+
+pub const fn usable_page_size(page_size: usize) -> usize {
+    page_size - PAGE_CHECKSUM_SIZE
+}
 
 pub const NODE_TYPE_OFFSET: usize = 0;
 pub const NODE_TYPE_SIZE: usize = mem::size_of::<u8>();
@@ -30,21 +50,76 @@ pub const LEAF_NODE_NUM_CELLS_OFFSET: usize = COMMON_NODE_HEADER_SIZE;
 pub const LEAF_NODE_NEXT_LEAF_SIZE: usize = mem::size_of::<u32>();
 pub const LEAF_NODE_NEXT_LEAF_OFFSET: usize = LEAF_NODE_NUM_CELLS_OFFSET + LEAF_NODE_NUM_CELLS_SIZE;
 
-pub const LEAF_NODE_HEADER_SIZE: usize = COMMON_NODE_HEADER_SIZE + LEAF_NODE_NUM_CELLS_SIZE + LEAF_NODE_NEXT_LEAF_SIZE;
-
-pub const LEAF_NODE_KEY_SIZE: usize = mem::size_of::<u32>();
-pub const LEAF_NODE_KEY_OFFSET: usize = 0;
-
-pub const LEAF_NODE_VALUE_SIZE: usize = ROW_SIZE;
+pub const LEAF_NODE_PREV_LEAF_SIZE: usize = mem::size_of::<u32>();
+pub const LEAF_NODE_PREV_LEAF_OFFSET: usize = LEAF_NODE_NEXT_LEAF_OFFSET + LEAF_NODE_NEXT_LEAF_SIZE;
 
-pub const LEAF_NODE_CELL_SIZE: usize = LEAF_NODE_KEY_SIZE + LEAF_NODE_VALUE_SIZE;
+// Cells are packed at the end of the page, growing down towards the cell pointer
+// array after the header. Bytes left behind by removed cells are counted as
+// fragmented until the page is defragmented.
+pub const LEAF_NODE_CELL_CONTENT_SIZE: usize = mem::size_of::<u16>();
+pub const LEAF_NODE_CELL_CONTENT_OFFSET: usize = LEAF_NODE_PREV_LEAF_OFFSET + LEAF_NODE_PREV_LEAF_SIZE;
 
-pub const LEAF_NODE_SPACE_FOR_CELLS: usize = PAGE_SIZE - LEAF_NODE_HEADER_SIZE;
+pub const LEAF_NODE_FRAGMENTED_BYTES_SIZE: usize = mem::size_of::<u16>();
+pub const LEAF_NODE_FRAGMENTED_BYTES_OFFSET: usize = LEAF_NODE_CELL_CONTENT_OFFSET + LEAF_NODE_CELL_CONTENT_SIZE;
 
-pub const LEAF_NODE_MAX_CELLS: usize = LEAF_NODE_SPACE_FOR_CELLS / LEAF_NODE_CELL_SIZE;
+pub const LEAF_NODE_HEADER_SIZE: usize = LEAF_NODE_FRAGMENTED_BYTES_OFFSET + LEAF_NODE_FRAGMENTED_BYTES_SIZE;
 
-pub const LEAF_NODE_RIGHT_SPLIT_COUNT: usize = (LEAF_NODE_MAX_CELLS + 1) / 2;
-pub const LEAF_NODE_LEFT_SPLIT_COUNT: usize = LEAF_NODE_MAX_CELLS + 1 - LEAF_NODE_RIGHT_SPLIT_COUNT;
+// Leaf Node Body Layout: one cell offset per cell in key order. A cell is the
+// size of its payload, a serialized row whose id is the key, then the payload.
+// A payload too large for the leaf keeps only a prefix in the cell, followed by
+// the first page of the overflow chain holding the rest.
+pub const LEAF_NODE_SLOT_SIZE: usize = mem::size_of::<u16>();
+pub const LEAF_NODE_PAYLOAD_SIZE_SIZE: usize = mem::size_of::<u32>();
+pub const LEAF_NODE_PAYLOAD_OFFSET: usize = LEAF_NODE_PAYLOAD_SIZE_SIZE;
+pub const LEAF_NODE_KEY_SIZE: usize = mem::size_of::<u32>();
+pub const LEAF_NODE_KEY_OFFSET: usize = LEAF_NODE_PAYLOAD_OFFSET + ID_OFFSET;
+pub const LEAF_NODE_OVERFLOW_PAGE_SIZE: usize = mem::size_of::<u32>();
+pub const LEAF_NODE_MIN_CELL_SIZE: usize = LEAF_NODE_PAYLOAD_SIZE_SIZE + ROW_MIN_SIZE;
+const _: () = assert!(usable_page_size(MAX_PAGE_SIZE) <= u16::MAX as usize, "cell offsets must fit in a slot");
+
+pub const fn leaf_node_space_for_cells(page_size: usize) -> usize {
+    usable_page_size(page_size) - LEAF_NODE_HEADER_SIZE
+}
+
+// the most cells a leaf can hold, all of them with an empty username and email
+pub const fn leaf_node_max_cells(page_size: usize) -> usize {
+    leaf_node_space_for_cells(page_size) / (LEAF_NODE_MIN_CELL_SIZE + LEAF_NODE_SLOT_SIZE)
+}
+
+// A cell and its slot take at most a quarter of a leaf, so a full leaf and the
+// cell that did not fit always split into two leaves that fit
+pub const fn leaf_node_max_cell_size(page_size: usize) -> usize {
+    leaf_node_space_for_cells(page_size) / 4 - LEAF_NODE_SLOT_SIZE
+}
+
+// payloads up to this size are kept whole in the leaf, larger ones spill
+pub const fn leaf_node_max_local_payload(page_size: usize) -> usize {
+    leaf_node_max_cell_size(page_size) - LEAF_NODE_PAYLOAD_SIZE_SIZE
+}
+
+// the prefix a spilled payload keeps in the leaf, its cell is as large as the
+// largest cell that did not spill
+pub const fn leaf_node_spilled_local_payload(page_size: usize) -> usize {
+    leaf_node_max_local_payload(page_size) - LEAF_NODE_OVERFLOW_PAGE_SIZE
+}
+const _: () = assert!(
+    leaf_node_spilled_local_payload(MIN_PAGE_SIZE) >= ID_SIZE,
+    "a spilled payload must keep its key in the leaf"
+);
+
+pub const fn leaf_node_cell_size(payload_size: usize, page_size: usize) -> usize {
+    if payload_size <= leaf_node_max_local_payload(page_size) {
+        LEAF_NODE_PAYLOAD_SIZE_SIZE + payload_size
+    } else {
+        leaf_node_max_cell_size(page_size)
+    }
+}
+
+// a non-root leaf using less than this much space is merged or refilled after a delete,
+// refilling from a sibling always brings it back above this
+pub const fn leaf_node_min_used_space(page_size: usize) -> usize {
+    leaf_node_space_for_cells(page_size) / 4
+}
 
 // Internal Node Header Layout
 pub const INTERNAL_NODE_NUM_KEYS_SIZE: usize = mem::size_of::<u32>();
@@ -60,6 +135,97 @@ pub const INTERNAL_NODE_KEY_SIZE: usize = mem::size_of::<u32>();
 pub const INTERNAL_NODE_CHILD_SIZE: usize = mem::size_of::<usize>();
 pub const INTERNAL_NODE_CELL_SIZE: usize = INTERNAL_NODE_CHILD_SIZE + INTERNAL_NODE_KEY_SIZE;
 
-pub const INTERNAL_NODE_MAX_CELLS: usize = 3;
-
-pub const INVALID_PAGE_NUMBER: usize = std::usize::MAX;
+pub const fn internal_node_max_cells(page_size: usize) -> usize {
+    (usable_page_size(page_size) - INTERNAL_NODE_HEADER_SIZE) / INTERNAL_NODE_CELL_SIZE
+}
+
+pub const fn internal_node_min_cells(page_size: usize) -> usize {
+    internal_node_max_cells(page_size) / 2
+}
+
+pub const INVALID_PAGE_NUMBER: usize = usize::MAX;
+
+// File Header Layout (page 0)
+pub const HEADER_PAGE_NUM: usize = 0;
+pub const HEADER_MAGIC: &[u8] = b"simpl_db format\0";
+pub const HEADER_FORMAT_VERSION: u32 = 5;
+
+pub const HEADER_MAGIC_OFFSET: usize = 0;
+pub const HEADER_MAGIC_SIZE: usize = HEADER_MAGIC.len();
+pub const HEADER_VERSION_OFFSET: usize = HEADER_MAGIC_OFFSET + HEADER_MAGIC_SIZE;
+pub const HEADER_VERSION_SIZE: usize = mem::size_of::<u32>();
+pub const HEADER_PAGE_SIZE_OFFSET: usize = HEADER_VERSION_OFFSET + HEADER_VERSION_SIZE;
+pub const HEADER_PAGE_SIZE_SIZE: usize = mem::size_of::<u32>();
+pub const HEADER_ROOT_PAGE_OFFSET: usize = HEADER_PAGE_SIZE_OFFSET + HEADER_PAGE_SIZE_SIZE;
+pub const HEADER_ROOT_PAGE_SIZE: usize = mem::size_of::<u32>();
+pub const HEADER_FREE_LIST_HEAD_OFFSET: usize = HEADER_ROOT_PAGE_OFFSET + HEADER_ROOT_PAGE_SIZE;
+pub const HEADER_FREE_LIST_HEAD_SIZE: usize = mem::size_of::<u32>();
+pub const HEADER_PAGE_COUNT_OFFSET: usize = HEADER_FREE_LIST_HEAD_OFFSET + HEADER_FREE_LIST_HEAD_SIZE;
+pub const HEADER_PAGE_COUNT_SIZE: usize = mem::size_of::<u32>();
+pub const HEADER_SIZE: usize = HEADER_PAGE_COUNT_OFFSET + HEADER_PAGE_COUNT_SIZE;
+const _: () = assert!(HEADER_SIZE <= usable_page_size(MIN_PAGE_SIZE), "file header must fit in one page");
+
+// Free page layout: a type byte that no node uses, followed by the next free page
+// (0 ends the list, page 0 always holds the header)
+pub const FREE_PAGE_TYPE: u8 = 2;
+pub const FREE_PAGE_NEXT_OFFSET: usize = COMMON_NODE_HEADER_SIZE;
+pub const FREE_PAGE_NEXT_SIZE: usize = mem::size_of::<u32>();
+
+// Overflow page layout: a type byte that no node uses, the next page of the
+// chain (0 ends it) and as much of the spilled payload as fits
+pub const OVERFLOW_PAGE_TYPE: u8 = 3;
+pub const OVERFLOW_PAGE_NEXT_OFFSET: usize = COMMON_NODE_HEADER_SIZE;
+pub const OVERFLOW_PAGE_NEXT_SIZE: usize = mem::size_of::<u32>();
+pub const OVERFLOW_PAGE_HEADER_SIZE: usize = OVERFLOW_PAGE_NEXT_OFFSET + OVERFLOW_PAGE_NEXT_SIZE;
+
+pub const fn overflow_page_capacity(page_size: usize) -> usize {
+    usable_page_size(page_size) - OVERFLOW_PAGE_HEADER_SIZE
+}
+
+// the root node lives right after the header page
+pub const DEFAULT_ROOT_PAGE_NUM: usize = 1;
+
+// Write-ahead log layout (<db file>-wal)
+pub const WAL_MAGIC: &[u8] = b"simpl_db wal\0\0\0\0";
+pub const WAL_FORMAT_VERSION: u32 = 1;
+pub const WAL_MAGIC_OFFSET: usize = 0;
+pub const WAL_MAGIC_SIZE: usize = WAL_MAGIC.len();
+pub const WAL_VERSION_OFFSET: usize = WAL_MAGIC_OFFSET + WAL_MAGIC_SIZE;
+pub const WAL_VERSION_SIZE: usize = mem::size_of::<u32>();
+pub const WAL_PAGE_SIZE_OFFSET: usize = WAL_VERSION_OFFSET + WAL_VERSION_SIZE;
+pub const WAL_PAGE_SIZE_SIZE: usize = mem::size_of::<u32>();
+pub const WAL_HEADER_SIZE: usize = WAL_PAGE_SIZE_OFFSET + WAL_PAGE_SIZE_SIZE;
+
+// Every record starts with this header. Page frames are followed by the page,
+// commit records have no body and carry the number of pages in the database.
+pub const WAL_RECORD_PAGE_NUM_OFFSET: usize = 0;
+pub const WAL_RECORD_PAGE_NUM_SIZE: usize = mem::size_of::<u32>();
+pub const WAL_RECORD_PAGE_COUNT_OFFSET: usize = WAL_RECORD_PAGE_NUM_OFFSET + WAL_RECORD_PAGE_NUM_SIZE;
+pub const WAL_RECORD_PAGE_COUNT_SIZE: usize = mem::size_of::<u32>();
+pub const WAL_RECORD_CHECKSUM_OFFSET: usize = WAL_RECORD_PAGE_COUNT_OFFSET + WAL_RECORD_PAGE_COUNT_SIZE;
+pub const WAL_RECORD_CHECKSUM_SIZE: usize = mem::size_of::<u32>();
+pub const WAL_RECORD_HEADER_SIZE: usize = WAL_RECORD_CHECKSUM_OFFSET + WAL_RECORD_CHECKSUM_SIZE;
+pub const WAL_COMMIT_RECORD: u32 = u32::MAX;
+// the log is copied into the database file once it holds this many frames
+pub const WAL_AUTOCHECKPOINT_FRAMES: usize = 1000;
+
+// Rollback journal layout (<db file>-journal)
+pub const JOURNAL_MAGIC: &[u8] = b"simpl_db journal";
+pub const JOURNAL_FORMAT_VERSION: u32 = 1;
+pub const JOURNAL_MAGIC_OFFSET: usize = 0;
+pub const JOURNAL_MAGIC_SIZE: usize = JOURNAL_MAGIC.len();
+pub const JOURNAL_VERSION_OFFSET: usize = JOURNAL_MAGIC_OFFSET + JOURNAL_MAGIC_SIZE;
+pub const JOURNAL_VERSION_SIZE: usize = mem::size_of::<u32>();
+pub const JOURNAL_PAGE_SIZE_OFFSET: usize = JOURNAL_VERSION_OFFSET + JOURNAL_VERSION_SIZE;
+pub const JOURNAL_PAGE_SIZE_SIZE: usize = mem::size_of::<u32>();
+// number of pages the database file had before the transaction
+pub const JOURNAL_PAGE_COUNT_OFFSET: usize = JOURNAL_PAGE_SIZE_OFFSET + JOURNAL_PAGE_SIZE_SIZE;
+pub const JOURNAL_PAGE_COUNT_SIZE: usize = mem::size_of::<u32>();
+pub const JOURNAL_HEADER_SIZE: usize = JOURNAL_PAGE_COUNT_OFFSET + JOURNAL_PAGE_COUNT_SIZE;
+
+// every record is a page number and a checksum followed by the original page
+pub const JOURNAL_RECORD_PAGE_NUM_OFFSET: usize = 0;
+pub const JOURNAL_RECORD_PAGE_NUM_SIZE: usize = mem::size_of::<u32>();
+pub const JOURNAL_RECORD_CHECKSUM_OFFSET: usize = JOURNAL_RECORD_PAGE_NUM_OFFSET + JOURNAL_RECORD_PAGE_NUM_SIZE;
+pub const JOURNAL_RECORD_CHECKSUM_SIZE: usize = mem::size_of::<u32>();
+pub const JOURNAL_RECORD_HEADER_SIZE: usize = JOURNAL_RECORD_CHECKSUM_OFFSET + JOURNAL_RECORD_CHECKSUM_SIZE;
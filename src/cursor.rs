@@ -1,6 +1,10 @@
 use crate::table::Table;
-use crate::node::{leaf_node_num_cells, leaf_node_value, leaf_node_next_leaf};
+use crate::enums::DbError;
+use crate::node::{leaf_node_cell, leaf_node_key, leaf_node_num_cells, leaf_node_next_leaf, leaf_node_prev_leaf};
+use crate::overflow::cell_payload;
 
+/// Moving a cursor reads pages, a page that fails its checksum stops it with
+/// `DbError::CorruptPage`.
 pub struct Cursor<'a> {
     pub table: &'a mut Table,
     pub page_num: usize,
@@ -9,22 +13,85 @@ pub struct Cursor<'a> {
 }
 
 impl Cursor<'_> {
-    pub fn advance(&mut self) {
-        let page = self.table.pager.get_page(self.page_num);
+    /// Creates a cursor and pins its page so the pager keeps it cached.
+    pub fn new(table: &mut Table, page_num: usize, cell_num: usize, end_of_table: bool) -> Result<Cursor<'_>, DbError> {
+        table.pager.pin_page(page_num)?;
+        Ok(Cursor {
+            table,
+            page_num,
+            cell_num,
+            end_of_table,
+        })
+    }
+
+    pub fn advance(&mut self) -> Result<(), DbError> {
         self.cell_num += 1;
-        if self.cell_num >= leaf_node_num_cells(page) as usize {
+        self.skip_to_next_leaf()
+    }
+
+    /// Moves a cursor past the last cell of its leaf to the first cell of the
+    /// next leaf, or marks the end of the table when there is none.
+    pub fn skip_to_next_leaf(&mut self) -> Result<(), DbError> {
+        loop {
+            let page = self.table.pager.get_page_ref(self.page_num)?;
+            if self.cell_num < leaf_node_num_cells(page) as usize {
+                return Ok(());
+            }
             let next_node_num = leaf_node_next_leaf(page) as usize;
             if next_node_num == 0 {
                 self.end_of_table = true;
-            } else {
-                self.page_num = next_node_num;
-                self.cell_num = 0;
+                return Ok(());
+            }
+            self.table.pager.pin_page(next_node_num)?;
+            self.table.pager.unpin_page(self.page_num);
+            self.page_num = next_node_num;
+            self.cell_num = 0;
+        }
+    }
+
+    /// Moves to the previous cell, following `prev_leaf` from the first cell of a
+    /// leaf. Moving back from the first row marks the end of the table, which is
+    /// where a descending scan ends.
+    pub fn retreat(&mut self) -> Result<(), DbError> {
+        self.end_of_table = false;
+        loop {
+            if self.cell_num > 0 {
+                self.cell_num -= 1;
+                return Ok(());
+            }
+            let prev_node_num = leaf_node_prev_leaf(self.table.pager.get_page_ref(self.page_num)?) as usize;
+            if prev_node_num == 0 {
+                self.end_of_table = true;
+                return Ok(());
             }
+            self.table.pager.pin_page(prev_node_num)?;
+            self.table.pager.unpin_page(self.page_num);
+            self.page_num = prev_node_num;
+            self.cell_num = leaf_node_num_cells(self.table.pager.get_page_ref(prev_node_num)?) as usize;
+        }
+    }
+
+    /// Key of the cell under the cursor, or `None` at the end of the table or past
+    /// the last cell of the leaf.
+    pub fn cursor_key(&mut self) -> Result<Option<u32>, DbError> {
+        let page = self.table.pager.get_page_ref(self.page_num)?;
+        if self.end_of_table || self.cell_num >= leaf_node_num_cells(page) as usize {
+            return Ok(None);
         }
+        Ok(Some(u32::from_le_bytes(leaf_node_key(page, self.cell_num).try_into().unwrap())))
     }
 
-    pub fn cursor_value(&mut self) -> &mut [u8] {
-        let page = self.table.pager.get_page(self.page_num);
-        leaf_node_value(page, self.cell_num)
+    /// The serialized row under the cursor, put back together from its overflow
+    /// chain when it spilled out of the leaf.
+    pub fn cursor_value(&mut self) -> Result<Vec<u8>, DbError> {
+        let page = self.table.pager.get_page_ref(self.page_num)?;
+        let cell = leaf_node_cell(page, self.cell_num).to_vec();
+        cell_payload(&mut self.table.pager, &cell)
     }
-}
\ No newline at end of file
+}
+
+impl Drop for Cursor<'_> {
+    fn drop(&mut self) {
+        self.table.pager.unpin_page(self.page_num);
+    }
+}
@@ -5,10 +5,78 @@ pub enum NodeType {
     NodeLeaf,
 }
 
+#[allow(clippy::enum_variant_names)]
 pub enum ExecuteResult {
     ExecuteSuccess,
     ExecuteTableDuplicateKey,
-    ExecuteTableFull,
+    ExecuteRecordNotFound,
+    ExecuteTransactionOpen,
+    ExecuteNoTransaction,
+    ExecuteNoSuchSavepoint,
+    ExecuteRowTooLarge,
+    // the statement hit a page it could not read
+    ExecuteError(DbError),
+}
+
+/// How committed changes reach the database file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JournalMode {
+    // changed pages are appended to a write-ahead log and checkpointed later
+    Wal,
+    // original pages are saved to a rollback journal before the file is overwritten
+    Rollback,
+    // pages are written straight to the file, a crash can corrupt it
+    Off,
+}
+
+impl JournalMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "wal" => Some(JournalMode::Wal),
+            "journal" => Some(JournalMode::Rollback),
+            "off" => Some(JournalMode::Off),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            JournalMode::Wal => "wal",
+            JournalMode::Rollback => "journal",
+            JournalMode::Off => "off",
+        }
+    }
+}
+
+/// How hard commits and checkpoints wait for data to reach the disk.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Synchronous {
+    // nothing is synced, a power loss can corrupt the database
+    Off,
+    // synced where a power loss could otherwise corrupt the database, the last
+    // commits before it may still be lost
+    Normal,
+    // every commit is durable once it returns
+    Full,
+}
+
+impl Synchronous {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "off" => Some(Synchronous::Off),
+            "normal" => Some(Synchronous::Normal),
+            "full" => Some(Synchronous::Full),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Synchronous::Off => "off",
+            Synchronous::Normal => "normal",
+            Synchronous::Full => "full",
+        }
+    }
 }
 
 pub enum MetaCommandResult {
@@ -16,9 +84,39 @@ pub enum MetaCommandResult {
     MetaCommandUnrecognizedCommand,
 }
 
+#[allow(clippy::enum_variant_names)]
 pub enum PrepareResult {
     PrepareSuccess(Statement),
     PrepareUnrecognizedStatement,
     PrepareSyntaxError,
     PrepareNegativeId,
-}
\ No newline at end of file
+}
+
+#[derive(Debug)]
+pub enum DbError {
+    Io(std::io::Error),
+    InvalidFile(String),
+    InvalidArgument(String),
+    TransactionOpen,
+    // a page whose checksum does not match its contents, or that is not the
+    // kind of page the page linking to it expects
+    CorruptPage(usize),
+}
+
+impl std::fmt::Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbError::Io(err) => write!(f, "I/O error: {}", err),
+            DbError::InvalidFile(reason) => write!(f, "Invalid database file: {}", reason),
+            DbError::InvalidArgument(reason) => write!(f, "Invalid argument: {}", reason),
+            DbError::TransactionOpen => write!(f, "Not allowed while a transaction is open"),
+            DbError::CorruptPage(page_num) => write!(f, "Page {} is corrupt", page_num),
+        }
+    }
+}
+
+impl From<std::io::Error> for DbError {
+    fn from(err: std::io::Error) -> Self {
+        DbError::Io(err)
+    }
+}
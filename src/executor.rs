@@ -1,29 +1,171 @@
-use crate::enums::ExecuteResult;
+use crate::constants::MAX_FIELD_SIZE;
+use crate::cursor::Cursor;
+use crate::enums::{DbError, ExecuteResult};
 use crate::node;
 use crate::row::Row;
-use crate::statement::{Statement, StatementType};
+use std::ops::Bound;
+use crate::statement::{KeyRange, RowUpdate, ScanOrder, Statement, StatementType};
 use crate::table::Table;
 
 pub fn execute_statement(statement: &Statement, table: &mut Table) -> ExecuteResult {
-    match statement.statement_type {
-        StatementType::StatementInsert => execute_insert(statement, table),
-        StatementType::StatementSelect => execute_select(statement, table),
-    }
+    let result = match &statement.statement_type {
+        StatementType::StatementInsert => table.run_statement(|table| execute_insert(statement, table)),
+        StatementType::StatementSelect(range, order) => table.run_statement(|table| execute_select(range, *order, table)),
+        StatementType::StatementDelete(id) => table.run_statement(|table| execute_delete(*id, table)),
+        StatementType::StatementUpdate(id, update) => table.run_statement(|table| execute_update(*id, update, table)),
+        StatementType::StatementBegin => return execute_begin(table),
+        StatementType::StatementCommit => return execute_commit(table),
+        StatementType::StatementRollback => return execute_rollback(table),
+        StatementType::StatementSavepoint(name) => return execute_savepoint(name, table),
+        StatementType::StatementRelease(name) => return execute_release(name, table),
+        StatementType::StatementRollbackTo(name) => return execute_rollback_to(name, table),
+    };
+    // a page the statement could not read is reported instead of ending the session
+    result.unwrap_or_else(ExecuteResult::ExecuteError)
 }
 
-fn execute_insert(statement: &Statement, table: &mut Table) -> ExecuteResult {
+fn execute_insert(statement: &Statement, table: &mut Table) -> Result<ExecuteResult, DbError> {
     let row_to_insert = statement.row_to_insert.as_ref().unwrap();
-    let mut cursor = table.table_find(row_to_insert.id as u32);
-    node::leaf_node_insert(&mut cursor, row_to_insert.id as u32, row_to_insert);
+    if !row_fits(row_to_insert) {
+        return Ok(ExecuteResult::ExecuteRowTooLarge);
+    }
+    let mut cursor = table.table_find(row_to_insert.id as u32)?;
+    node::leaf_node_insert(&mut cursor, row_to_insert.id as u32, row_to_insert)
+}
+
+fn execute_delete(id: u32, table: &mut Table) -> Result<ExecuteResult, DbError> {
+    let mut cursor = table.table_find(id)?;
+    if cursor.cursor_key()? != Some(id) {
+        return Ok(ExecuteResult::ExecuteRecordNotFound);
+    }
+    node::leaf_node_delete(&mut cursor)?;
+    Ok(ExecuteResult::ExecuteSuccess)
+}
+
+fn execute_update(id: u32, update: &RowUpdate, table: &mut Table) -> Result<ExecuteResult, DbError> {
+    let mut cursor = table.table_find(id)?;
+    if cursor.cursor_key()? != Some(id) {
+        return Ok(ExecuteResult::ExecuteRecordNotFound);
+    }
+    let mut row = cursor_row(&mut cursor)?;
+    if let Some(username) = &update.username {
+        row.username = username.clone();
+    }
+    if let Some(email) = &update.email {
+        row.email = email.clone();
+    }
+    if !row_fits(&row) {
+        return Ok(ExecuteResult::ExecuteRowTooLarge);
+    }
+    node::leaf_node_update(&mut cursor, &row)?;
+    Ok(ExecuteResult::ExecuteSuccess)
+}
+
+// Rows too large for a leaf spill to overflow pages, only the field lengths limit their size.
+fn row_fits(row: &Row) -> bool {
+    row.username.len() <= MAX_FIELD_SIZE && row.email.len() <= MAX_FIELD_SIZE
+}
+
+fn execute_begin(table: &mut Table) -> ExecuteResult {
+    if table.pager.in_transaction() {
+        return ExecuteResult::ExecuteTransactionOpen;
+    }
+    table.pager.begin();
+    ExecuteResult::ExecuteSuccess
+}
+
+fn execute_commit(table: &mut Table) -> ExecuteResult {
+    if !table.pager.in_transaction() {
+        return ExecuteResult::ExecuteNoTransaction;
+    }
+    table.commit();
+    ExecuteResult::ExecuteSuccess
+}
+
+fn execute_rollback(table: &mut Table) -> ExecuteResult {
+    if !table.pager.in_transaction() {
+        return ExecuteResult::ExecuteNoTransaction;
+    }
+    table.pager.rollback();
     ExecuteResult::ExecuteSuccess
 }
 
-fn execute_select(_statement: &Statement, table: &mut Table) -> ExecuteResult {
-    let mut cursor = table.table_start();
-    while cursor.end_of_table == false {
-        let row = Row::deserialize_row(cursor.cursor_value());
-        println!("({}, {}, {})", row.id, row.username, row.email);
-        cursor.advance();
+fn execute_savepoint(name: &str, table: &mut Table) -> ExecuteResult {
+    if !table.pager.in_transaction() {
+        return ExecuteResult::ExecuteNoTransaction;
     }
+    table.pager.savepoint(name);
     ExecuteResult::ExecuteSuccess
-}
\ No newline at end of file
+}
+
+fn execute_release(name: &str, table: &mut Table) -> ExecuteResult {
+    if !table.pager.in_transaction() {
+        return ExecuteResult::ExecuteNoTransaction;
+    }
+    if !table.pager.release(name) {
+        return ExecuteResult::ExecuteNoSuchSavepoint;
+    }
+    ExecuteResult::ExecuteSuccess
+}
+
+fn execute_rollback_to(name: &str, table: &mut Table) -> ExecuteResult {
+    if !table.pager.in_transaction() {
+        return ExecuteResult::ExecuteNoTransaction;
+    }
+    if !table.pager.rollback_to(name) {
+        return ExecuteResult::ExecuteNoSuchSavepoint;
+    }
+    ExecuteResult::ExecuteSuccess
+}
+
+fn execute_select(range: &KeyRange, order: ScanOrder, table: &mut Table) -> Result<ExecuteResult, DbError> {
+    scan_range(table, range, order, |row| println!("({}, {}, {})", row.id, row.username, row.email))?;
+    Ok(ExecuteResult::ExecuteSuccess)
+}
+
+/// Calls `visit` with every row in `range` in the given order. The scan seeks to
+/// the end of the range it starts from and stops at the first key past the other,
+/// or at the first page it cannot read.
+pub fn scan_range(
+    table: &mut Table,
+    range: &KeyRange,
+    order: ScanOrder,
+    mut visit: impl FnMut(&Row),
+) -> Result<(), DbError> {
+    match order {
+        ScanOrder::Ascending => {
+            let mut cursor = match range.start {
+                Bound::Included(start) => table.seek_ge(start)?,
+                Bound::Excluded(start) => table.seek_gt(start)?,
+                Bound::Unbounded => table.table_start()?,
+            };
+            while let Some(key) = cursor.cursor_key()? {
+                if range.ends_before(key) {
+                    break;
+                }
+                visit(&cursor_row(&mut cursor)?);
+                cursor.advance()?;
+            }
+        }
+        ScanOrder::Descending => {
+            let mut cursor = match range.end {
+                Bound::Included(end) => table.seek_le(end)?,
+                Bound::Excluded(end) => table.seek_lt(end)?,
+                Bound::Unbounded => table.table_end()?,
+            };
+            while let Some(key) = cursor.cursor_key()? {
+                if range.starts_after(key) {
+                    break;
+                }
+                visit(&cursor_row(&mut cursor)?);
+                cursor.retreat()?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// A row that cannot be decoded is reported as a corrupt page, the leaf holding it.
+fn cursor_row(cursor: &mut Cursor) -> Result<Row, DbError> {
+    Row::try_deserialize_row(&cursor.cursor_value()?).ok_or(DbError::CorruptPage(cursor.page_num))
+}
@@ -0,0 +1,106 @@
+use crate::constants::*;
+use crate::enums::DbError;
+
+/// Contents of the header page at the start of every database file.
+pub struct FileHeader {
+    pub version: u32,
+    pub page_size: u32,
+    pub root_page_num: u32,
+    pub free_list_head: u32,
+    pub page_count: u32,
+}
+
+impl FileHeader {
+    pub fn new(page_size: usize, root_page_num: usize, page_count: usize) -> Self {
+        FileHeader {
+            version: HEADER_FORMAT_VERSION,
+            page_size: page_size as u32,
+            root_page_num: root_page_num as u32,
+            free_list_head: 0,
+            page_count: page_count as u32,
+        }
+    }
+
+    pub fn read(page: &[u8]) -> Result<Self, DbError> {
+        if &page[HEADER_MAGIC_OFFSET..HEADER_MAGIC_OFFSET + HEADER_MAGIC_SIZE] != HEADER_MAGIC {
+            return Err(DbError::InvalidFile(
+                "not a simpl_db database (bad magic number)".to_string(),
+            ));
+        }
+        let header = FileHeader {
+            version: read_u32(page, HEADER_VERSION_OFFSET),
+            page_size: read_u32(page, HEADER_PAGE_SIZE_OFFSET),
+            root_page_num: read_u32(page, HEADER_ROOT_PAGE_OFFSET),
+            free_list_head: read_u32(page, HEADER_FREE_LIST_HEAD_OFFSET),
+            page_count: read_u32(page, HEADER_PAGE_COUNT_OFFSET),
+        };
+        if header.version != HEADER_FORMAT_VERSION {
+            return Err(DbError::InvalidFile(format!(
+                "unsupported format version {} (expected {})",
+                header.version, HEADER_FORMAT_VERSION
+            )));
+        }
+        validate_page_size(header.page_size as usize)?;
+        if header.root_page_num as usize == HEADER_PAGE_NUM
+            || header.root_page_num >= header.page_count
+        {
+            return Err(DbError::InvalidFile(format!(
+                "root page {} is outside the {} pages of the file",
+                header.root_page_num, header.page_count
+            )));
+        }
+        if header.free_list_head >= header.page_count {
+            return Err(DbError::InvalidFile(format!(
+                "free list head {} is outside the {} pages of the file",
+                header.free_list_head, header.page_count
+            )));
+        }
+        Ok(header)
+    }
+
+    pub fn write(&self, page: &mut [u8]) {
+        page[HEADER_MAGIC_OFFSET..HEADER_MAGIC_OFFSET + HEADER_MAGIC_SIZE].copy_from_slice(HEADER_MAGIC);
+        write_u32(page, HEADER_VERSION_OFFSET, self.version);
+        write_u32(page, HEADER_PAGE_SIZE_OFFSET, self.page_size);
+        write_u32(page, HEADER_ROOT_PAGE_OFFSET, self.root_page_num);
+        write_u32(page, HEADER_FREE_LIST_HEAD_OFFSET, self.free_list_head);
+        write_u32(page, HEADER_PAGE_COUNT_OFFSET, self.page_count);
+    }
+}
+
+/// First page of the free list, 0 when it is empty.
+pub fn free_list_head(page: &[u8]) -> usize {
+    read_u32(page, HEADER_FREE_LIST_HEAD_OFFSET) as usize
+}
+
+pub fn set_free_list_head(page: &mut [u8], page_num: usize) {
+    write_u32(page, HEADER_FREE_LIST_HEAD_OFFSET, page_num as u32);
+}
+
+pub fn page_count(page: &[u8]) -> usize {
+    read_u32(page, HEADER_PAGE_COUNT_OFFSET) as usize
+}
+
+pub fn set_page_count(page: &mut [u8], page_count: usize) {
+    write_u32(page, HEADER_PAGE_COUNT_OFFSET, page_count as u32);
+}
+
+/// Page sizes must be a power of two between `MIN_PAGE_SIZE` and `MAX_PAGE_SIZE`.
+pub fn validate_page_size(page_size: usize) -> Result<(), DbError> {
+    if !page_size.is_power_of_two() || !(MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&page_size) {
+        return Err(DbError::InvalidFile(format!(
+            "page size {} is not a power of two between {} and {}",
+            page_size, MIN_PAGE_SIZE, MAX_PAGE_SIZE
+        )));
+    }
+    Ok(())
+}
+
+/// Reads the little-endian u32 at `offset`, the byte order of every on-disk field.
+pub fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+pub fn write_u32(bytes: &mut [u8], offset: usize, value: u32) {
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
@@ -0,0 +1,293 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use crate::constants::*;
+use crate::enums::DbError;
+use crate::header::{validate_page_size, FileHeader};
+use crate::node::{
+    get_node_parent, internal_node_children, internal_node_keys, internal_node_num_keys,
+    leaf_node_cell_content_start, leaf_node_cell_offset, leaf_node_fragmented_bytes, leaf_node_next_leaf,
+    leaf_node_num_cells, leaf_node_prev_leaf,
+};
+use crate::overflow::{cell_overflow_page, cell_payload_size, cell_size_within, overflow_page_next};
+use crate::pager::page_checksum_matches;
+use crate::row::Row;
+
+/// A database file opened read-only. Pages are read straight from the file, so
+/// frames still waiting in the write-ahead log are not seen and nothing is ever
+/// written back.
+pub struct PageFile {
+    file: File,
+    pub page_size: usize,
+    pub num_pages: usize,
+    // why the header could not be read when the page size came from elsewhere
+    pub header_error: Option<DbError>,
+}
+
+impl PageFile {
+    /// Opens the file with the page size recorded in its header, or with
+    /// `page_size` when one is given. A file whose header cannot be read is
+    /// still opened when the page size can be guessed, the header's error is
+    /// kept in `header_error`.
+    pub fn open(file_name: &str, page_size: Option<usize>) -> Result<Self, DbError> {
+        let mut file = File::open(file_name)?;
+        let file_size = file.metadata()?.len() as usize;
+        let mut header_page = vec![0; MIN_PAGE_SIZE];
+        if file.read_exact(&mut header_page).is_err() {
+            return Err(DbError::InvalidFile("file is too short to hold a header".to_string()));
+        }
+        let (page_size, header_error) = match (page_size, FileHeader::read(&header_page)) {
+            (Some(page_size), header) => {
+                validate_page_size(page_size)?;
+                (page_size, header.err())
+            }
+            (None, Ok(header)) => (header.page_size as usize, None),
+            (None, Err(err)) => match guess_page_size(&mut file, file_size)? {
+                Some(page_size) => (page_size, Some(err)),
+                None => return Err(err),
+            },
+        };
+        Ok(PageFile {
+            file,
+            page_size,
+            num_pages: file_size / page_size,
+            header_error,
+        })
+    }
+
+    pub fn read_page(&mut self, page_num: usize) -> Result<Vec<u8>, DbError> {
+        if page_num >= self.num_pages {
+            return Err(DbError::InvalidArgument(format!(
+                "page {} is outside the {} pages of the file",
+                page_num, self.num_pages
+            )));
+        }
+        let mut page = vec![0; self.page_size];
+        self.file.seek(SeekFrom::Start((page_num * self.page_size) as u64))?;
+        self.file.read_exact(&mut page)?;
+        Ok(page)
+    }
+}
+
+// The page size at which page 1 passes its checksum. Every file has a page 1,
+// the root it was created with, so only the right size can match.
+fn guess_page_size(file: &mut File, file_size: usize) -> Result<Option<usize>, DbError> {
+    let mut page_size = MIN_PAGE_SIZE;
+    while page_size <= MAX_PAGE_SIZE && 2 * page_size <= file_size {
+        if file_size.is_multiple_of(page_size) {
+            let mut page = vec![0; page_size];
+            file.seek(SeekFrom::Start(page_size as u64))?;
+            file.read_exact(&mut page)?;
+            if page_checksum_matches(1, &page) {
+                return Ok(Some(page_size));
+            }
+        }
+        page_size *= 2;
+    }
+    Ok(None)
+}
+
+/// What a page holds, judged from its type byte.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PageType {
+    Header,
+    Internal,
+    Leaf,
+    Free,
+    Overflow,
+    Unknown(u8),
+}
+
+impl PageType {
+    pub fn of(page_num: usize, page: &[u8]) -> Self {
+        if page_num == HEADER_PAGE_NUM {
+            return PageType::Header;
+        }
+        // read directly, get_node_type reports anything but a node as corrupt
+        match page[NODE_TYPE_OFFSET] {
+            0 => PageType::Internal,
+            1 => PageType::Leaf,
+            FREE_PAGE_TYPE => PageType::Free,
+            OVERFLOW_PAGE_TYPE => PageType::Overflow,
+            node_type => PageType::Unknown(node_type),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PageType::Header => "header",
+            PageType::Internal => "internal",
+            PageType::Leaf => "leaf",
+            PageType::Free => "free",
+            PageType::Overflow => "overflow",
+            PageType::Unknown(_) => "unknown",
+        }
+    }
+}
+
+/// Sixteen bytes per line: the offset, the bytes in hex and the printable ones as text.
+pub fn hexdump(page: &[u8]) -> String {
+    let mut out = String::new();
+    for (line_num, line) in page.chunks(16).enumerate() {
+        let hex: Vec<String> = line.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let text: String = line
+            .iter()
+            .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+            .collect();
+        out.push_str(&format!("{:04x}  {:<47}  |{}|\n", line_num * 16, hex.join(" "), text));
+    }
+    out
+}
+
+/// Decodes the header fields of a page according to its type.
+pub fn describe_page(page_num: usize, page: &[u8]) -> String {
+    let page_type = PageType::of(page_num, page);
+    let mut out = format!("page {}: {}\n", page_num, page_type.name());
+    let checksum = if page_checksum_matches(page_num, page) { "ok" } else { "mismatch" };
+    out.push_str(&format!("  checksum: {}\n", checksum));
+    match page_type {
+        PageType::Header => match FileHeader::read(page) {
+            Ok(header) => {
+                out.push_str(&format!("  version: {}\n", header.version));
+                out.push_str(&format!("  page_size: {}\n", header.page_size));
+                out.push_str(&format!("  root_page: {}\n", header.root_page_num));
+                out.push_str(&format!("  free_list_head: {}\n", header.free_list_head));
+                out.push_str(&format!("  page_count: {}\n", header.page_count));
+            }
+            Err(err) => out.push_str(&format!("  {}\n", err)),
+        },
+        PageType::Internal | PageType::Leaf => {
+            out.push_str(&format!("  is_root: {}\n", page[IS_ROOT_OFFSET] != 0));
+            out.push_str(&format!("  parent: {}\n", get_node_parent(page)));
+            match page_type {
+                PageType::Internal => {
+                    out.push_str(&format!("  num_keys: {}\n", internal_node_num_keys(page)));
+                    let right_child = usize::from_le_bytes(
+                        page[INTERNAL_NODE_RIGHT_CHILD_OFFSET
+                            ..INTERNAL_NODE_RIGHT_CHILD_OFFSET + INTERNAL_NODE_RIGHT_CHILD_SIZE]
+                            .try_into()
+                            .unwrap(),
+                    );
+                    out.push_str(&format!("  right_child: {}\n", right_child));
+                }
+                _ => {
+                    out.push_str(&format!("  num_cells: {}\n", leaf_node_num_cells(page)));
+                    out.push_str(&format!("  next_leaf: {}\n", leaf_node_next_leaf(page)));
+                    out.push_str(&format!("  prev_leaf: {}\n", leaf_node_prev_leaf(page)));
+                    out.push_str(&format!("  cell_content_start: {}\n", leaf_node_cell_content_start(page)));
+                    out.push_str(&format!("  fragmented_bytes: {}\n", leaf_node_fragmented_bytes(page)));
+                }
+            }
+        }
+        PageType::Free => {
+            let next_free_page = u32::from_le_bytes(
+                page[FREE_PAGE_NEXT_OFFSET..FREE_PAGE_NEXT_OFFSET + FREE_PAGE_NEXT_SIZE].try_into().unwrap(),
+            );
+            out.push_str(&format!("  next_free_page: {}\n", next_free_page));
+        }
+        PageType::Overflow => {
+            out.push_str(&format!("  next_overflow_page: {}\n", overflow_page_next(page)));
+        }
+        PageType::Unknown(node_type) => out.push_str(&format!("  type byte: {}\n", node_type)),
+    }
+    out
+}
+
+/// Lists the cells of a node, leaf cells with their decoded rows. A cell count
+/// larger than the page can hold is reported and only the cells that fit are shown,
+/// a cell outside the page or holding a row that cannot be decoded is listed as corrupt.
+pub fn describe_cells(page_num: usize, page: &[u8]) -> String {
+    let mut out = String::new();
+    match PageType::of(page_num, page) {
+        PageType::Leaf => {
+            let num_cells = leaf_node_num_cells(page) as usize;
+            let max_cells = leaf_node_max_cells(page.len());
+            if num_cells > max_cells {
+                out.push_str(&format!("  {} cells recorded but only {} fit\n", num_cells, max_cells));
+            }
+            let slots_end = LEAF_NODE_HEADER_SIZE + num_cells.min(max_cells) * LEAF_NODE_SLOT_SIZE;
+            let content_end = usable_page_size(page.len());
+            for cell_num in 0..num_cells.min(max_cells) {
+                // a slot or a payload size gone bad must not take the dump down with it
+                let cell_offset = leaf_node_cell_offset(page, cell_num);
+                let cell_size = if cell_offset >= slots_end && cell_offset < content_end {
+                    cell_size_within(&page[cell_offset..content_end], page.len())
+                } else {
+                    None
+                };
+                let cell = match cell_size {
+                    Some(cell_size) => &page[cell_offset..cell_offset + cell_size],
+                    None => {
+                        out.push_str(&format!("  cell {}: corrupt cell at offset {}\n", cell_num, cell_offset));
+                        continue;
+                    }
+                };
+                let key = u32::from_le_bytes(
+                    cell[LEAF_NODE_KEY_OFFSET..LEAF_NODE_KEY_OFFSET + LEAF_NODE_KEY_SIZE].try_into().unwrap(),
+                );
+                // only the prefix of a spilled row is on this page
+                if let Some(overflow_page_num) = cell_overflow_page(cell, page.len()) {
+                    out.push_str(&format!(
+                        "  cell {}: key {} ({} bytes, spills to page {})\n",
+                        cell_num,
+                        key,
+                        cell_payload_size(cell),
+                        overflow_page_num
+                    ));
+                    continue;
+                }
+                match Row::try_deserialize_row(&cell[LEAF_NODE_PAYLOAD_OFFSET..]) {
+                    Some(row) => out.push_str(&format!(
+                        "  cell {}: key {} ({}, {}, {})\n",
+                        cell_num,
+                        key,
+                        row.id,
+                        row.username.trim_end_matches('\0'),
+                        row.email.trim_end_matches('\0')
+                    )),
+                    None => out.push_str(&format!("  cell {}: key {} corrupt cell, row cannot be decoded\n", cell_num, key)),
+                }
+            }
+        }
+        PageType::Internal => {
+            let num_keys = internal_node_num_keys(page) as usize;
+            let max_keys = internal_node_max_cells(page.len());
+            if num_keys > max_keys {
+                out.push_str(&format!("  {} keys recorded but only {} fit\n", num_keys, max_keys));
+                return out;
+            }
+            let children = internal_node_children(page);
+            for (cell_num, key) in internal_node_keys(page).into_iter().enumerate() {
+                out.push_str(&format!("  cell {}: child {} key {}\n", cell_num, children[cell_num], key));
+            }
+            out.push_str(&format!("  right child {}\n", children[num_keys]));
+        }
+        page_type => out.push_str(&format!("  a {} page has no cells\n", page_type.name())),
+    }
+    out
+}
+
+/// Counts the pages of each type in the file, and the pages failing their checksum.
+pub fn page_type_histogram(page_file: &mut PageFile) -> Result<String, DbError> {
+    let page_types = [PageType::Header, PageType::Internal, PageType::Leaf, PageType::Free, PageType::Overflow];
+    let mut counts = [0; 5];
+    let mut unknown_count = 0;
+    let mut mismatch_count = 0;
+    for page_num in 0..page_file.num_pages {
+        let page = page_file.read_page(page_num)?;
+        let page_type = PageType::of(page_num, &page);
+        match page_types.iter().position(|&known_type| known_type == page_type) {
+            Some(index) => counts[index] += 1,
+            None => unknown_count += 1,
+        }
+        if !page_checksum_matches(page_num, &page) {
+            mismatch_count += 1;
+        }
+    }
+    let mut out = String::new();
+    for (page_type, count) in page_types.iter().zip(counts) {
+        out.push_str(&format!("{}: {}\n", page_type.name(), count));
+    }
+    out.push_str(&format!("unknown: {}\n", unknown_count));
+    out.push_str(&format!("checksum mismatches: {}\n", mismatch_count));
+    Ok(out)
+}
@@ -0,0 +1,174 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use crate::checksum::crc32_update;
+use crate::constants::*;
+use crate::enums::{DbError, Synchronous};
+use crate::header::{read_u32, validate_page_size, write_u32};
+use crate::pager::sync_directory;
+
+/// Rollback journal kept next to the database file while a transaction runs.
+///
+/// Before a page in the database file is overwritten for the first time in a
+/// transaction its original contents are appended to the journal, and the
+/// journal is synced before the database file is touched. Deleting the journal
+/// commits the transaction. A journal still there when the database is opened
+/// belongs to a transaction that never finished, and copying its pages back
+/// undoes it.
+pub struct RollbackJournal {
+    file_name: String,
+    page_size: usize,
+    // open while a transaction has saved pages
+    file: Option<File>,
+    // number of pages in the database file when the transaction started
+    original_page_count: usize,
+    saved_pages: HashSet<usize>,
+    needs_sync: bool,
+    // set when the journal was created and its directory entry may not be on disk yet
+    needs_directory_sync: bool,
+}
+
+impl RollbackJournal {
+    pub fn new(db_file_name: &str, page_size: usize) -> Self {
+        RollbackJournal {
+            file_name: journal_file_name(db_file_name),
+            page_size,
+            file: None,
+            original_page_count: 0,
+            saved_pages: HashSet::new(),
+            needs_sync: false,
+            needs_directory_sync: false,
+        }
+    }
+
+    /// Saves the current contents of `page_num` in the database file unless the
+    /// transaction already saved it or the page did not exist when it started.
+    pub fn save_page(&mut self, page_num: usize, db_file: &mut File) -> std::io::Result<()> {
+        if self.file.is_none() {
+            self.start(db_file)?;
+        }
+        if page_num >= self.original_page_count || !self.saved_pages.insert(page_num) {
+            return Ok(());
+        }
+        let mut record = vec![0; JOURNAL_RECORD_HEADER_SIZE + self.page_size];
+        db_file.seek(SeekFrom::Start((page_num * self.page_size) as u64))?;
+        db_file.read_exact(&mut record[JOURNAL_RECORD_HEADER_SIZE..])?;
+        write_u32(&mut record, JOURNAL_RECORD_PAGE_NUM_OFFSET, page_num as u32);
+        let checksum = record_checksum(&record);
+        write_u32(&mut record, JOURNAL_RECORD_CHECKSUM_OFFSET, checksum);
+        let file = self.file.as_mut().unwrap();
+        file.seek(SeekFrom::End(0))?;
+        file.write_all(&record)?;
+        self.needs_sync = true;
+        Ok(())
+    }
+
+    fn start(&mut self, db_file: &mut File) -> std::io::Result<()> {
+        self.original_page_count = db_file.metadata()?.len() as usize / self.page_size;
+        let mut header = [0; JOURNAL_HEADER_SIZE];
+        header[JOURNAL_MAGIC_OFFSET..JOURNAL_MAGIC_OFFSET + JOURNAL_MAGIC_SIZE].copy_from_slice(JOURNAL_MAGIC);
+        write_u32(&mut header, JOURNAL_VERSION_OFFSET, JOURNAL_FORMAT_VERSION);
+        write_u32(&mut header, JOURNAL_PAGE_SIZE_OFFSET, self.page_size as u32);
+        write_u32(&mut header, JOURNAL_PAGE_COUNT_OFFSET, self.original_page_count as u32);
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&self.file_name)?;
+        file.write_all(&header)?;
+        self.file = Some(file);
+        self.needs_sync = true;
+        self.needs_directory_sync = true;
+        Ok(())
+    }
+
+    /// Makes the saved pages durable, must be called before the database file is
+    /// written. At `Synchronous::Full` a new journal's directory is synced too,
+    /// so the journal cannot vanish in a power loss.
+    pub fn sync(&mut self, synchronous: Synchronous) -> std::io::Result<()> {
+        if synchronous == Synchronous::Off {
+            return Ok(());
+        }
+        if let (Some(file), true) = (self.file.as_mut(), self.needs_sync) {
+            file.sync_data()?;
+            self.needs_sync = false;
+        }
+        if self.needs_directory_sync {
+            if synchronous == Synchronous::Full {
+                sync_directory(&self.file_name)?;
+            }
+            self.needs_directory_sync = false;
+        }
+        Ok(())
+    }
+
+    /// Ends the transaction by deleting the journal. The database file must
+    /// already be synced, otherwise a crash could lose the transaction.
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        if self.file.take().is_some() {
+            std::fs::remove_file(&self.file_name)?;
+        }
+        self.saved_pages.clear();
+        self.needs_sync = false;
+        self.needs_directory_sync = false;
+        Ok(())
+    }
+}
+
+/// Undoes the transaction recorded in a journal left behind by a crash, then
+/// deletes the journal. Does nothing when there is no journal.
+pub fn rollback_hot_journal(db_file_name: &str, db_file: &mut File) -> Result<(), DbError> {
+    let file_name = journal_file_name(db_file_name);
+    let mut file = match File::open(&file_name) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut header = [0; JOURNAL_HEADER_SIZE];
+    // without a complete header nothing was synced, so the database file was never written
+    if file.read_exact(&mut header).is_ok() {
+        if &header[JOURNAL_MAGIC_OFFSET..JOURNAL_MAGIC_OFFSET + JOURNAL_MAGIC_SIZE] != JOURNAL_MAGIC {
+            return Err(DbError::InvalidFile(
+                "not a simpl_db rollback journal (bad magic number)".to_string(),
+            ));
+        }
+        let version = read_u32(&header, JOURNAL_VERSION_OFFSET);
+        if version != JOURNAL_FORMAT_VERSION {
+            return Err(DbError::InvalidFile(format!(
+                "unsupported rollback journal version {} (expected {})",
+                version, JOURNAL_FORMAT_VERSION
+            )));
+        }
+        let page_size = read_u32(&header, JOURNAL_PAGE_SIZE_OFFSET) as usize;
+        validate_page_size(page_size)?;
+        let original_page_count = read_u32(&header, JOURNAL_PAGE_COUNT_OFFSET) as usize;
+
+        // a torn or mismatched record was being written when the crash happened,
+        // the page it holds had not been overwritten yet
+        let mut record = vec![0; JOURNAL_RECORD_HEADER_SIZE + page_size];
+        while file.read_exact(&mut record).is_ok() {
+            if record_checksum(&record) != read_u32(&record, JOURNAL_RECORD_CHECKSUM_OFFSET) {
+                break;
+            }
+            let page_num = read_u32(&record, JOURNAL_RECORD_PAGE_NUM_OFFSET) as usize;
+            db_file.seek(SeekFrom::Start((page_num * page_size) as u64))?;
+            db_file.write_all(&record[JOURNAL_RECORD_HEADER_SIZE..])?;
+        }
+        db_file.set_len((original_page_count * page_size) as u64)?;
+        db_file.sync_all()?;
+    }
+    drop(file);
+    std::fs::remove_file(&file_name)?;
+    Ok(())
+}
+
+fn journal_file_name(db_file_name: &str) -> String {
+    format!("{}-journal", db_file_name)
+}
+
+// Checksum of a record with its checksum field left out.
+fn record_checksum(record: &[u8]) -> u32 {
+    let crc = crc32_update(0, &record[..JOURNAL_RECORD_CHECKSUM_OFFSET]);
+    crc32_update(crc, &record[JOURNAL_RECORD_HEADER_SIZE..])
+}
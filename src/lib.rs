@@ -0,0 +1,23 @@
+pub mod bulk_load;
+pub mod check;
+pub mod checksum;
+pub mod config;
+pub mod constants;
+pub mod cursor;
+pub mod enums;
+pub mod executor;
+pub mod header;
+pub mod inspect;
+pub mod journal;
+pub mod node;
+pub mod overflow;
+pub mod pager;
+pub mod parser;
+pub mod row;
+pub mod statement;
+pub mod table;
+pub mod vacuum;
+pub mod wal;
+
+#[cfg(test)]
+mod tests;
@@ -1,34 +1,21 @@
-use enums::{ExecuteResult, MetaCommandResult, PrepareResult};
+use simpl_db::config::DbConfig;
+use simpl_db::constants::*;
+use simpl_db::enums::{ExecuteResult, JournalMode, MetaCommandResult, PrepareResult, Synchronous};
+use simpl_db::table::Table;
+use simpl_db::{bulk_load, check, executor, node, parser, vacuum};
 use std::io;
 use std::io::Write;
-use table::Table;
-
-mod constants;
-mod cursor;
-mod enums;
-mod executor;
-mod node;
-mod pager;
-mod row;
-mod statement;
-mod table;
-mod parser;
-
-#[cfg(test)]
-mod tests;
 
 const DEFAULT_DB_FILE_PATH: &str = ".\\myDb.db";
 
 fn main() {
-    let mut db_file_path: String = String::new();
-    std::env::args().skip(1).next().map(|arg| {
-        db_file_path = arg;
-    }).unwrap_or_else(|| {
-        db_file_path = DEFAULT_DB_FILE_PATH.to_string();
+    let (db_file_path, config) = parse_args();
+    let mut table = Table::open(&db_file_path, &config).unwrap_or_else(|err| {
+        eprintln!("Error opening '{}': {}", db_file_path, err);
+        std::process::exit(1);
     });
-    let mut table = Table::open(&db_file_path);
     loop {
-        print_prompt();
+        print_prompt(table.pager.in_transaction());
         let input = read_input();
         if input.starts_with(".") {
             match do_meta_command(&input, &mut table) {
@@ -58,7 +45,60 @@ fn main() {
         match executor::execute_statement(&statement, &mut table) {
             ExecuteResult::ExecuteSuccess => println!("Executed."),
             ExecuteResult::ExecuteTableDuplicateKey => println!("Error: Duplicate key."),
-            ExecuteResult::ExecuteTableFull => println!("Error: Table full."),
+            ExecuteResult::ExecuteRecordNotFound => println!("Error: Record not found."),
+            ExecuteResult::ExecuteTransactionOpen => println!("Error: A transaction is already open."),
+            ExecuteResult::ExecuteNoTransaction => println!("Error: No transaction is open."),
+            ExecuteResult::ExecuteNoSuchSavepoint => println!("Error: No such savepoint."),
+            ExecuteResult::ExecuteRowTooLarge => println!("Error: Row is too large."),
+            ExecuteResult::ExecuteError(err) => println!("Error: {}.", err),
+        }
+    }
+}
+
+// Usage: simpl_db [--page-size <bytes>] [--pool-size <frames>] [--journal-mode wal|journal|off]
+//                 [--synchronous off|normal|full] [db_file]
+fn parse_args() -> (String, DbConfig) {
+    let mut db_file_path = DEFAULT_DB_FILE_PATH.to_string();
+    let mut config = DbConfig::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--page-size" => config.page_size = parse_flag_value(&arg, args.next()),
+            "--pool-size" => config.pool_size = parse_flag_value(&arg, args.next()),
+            "--journal-mode" => config.journal_mode = parse_journal_mode(args.next()),
+            "--synchronous" => config.synchronous = parse_synchronous(args.next()),
+            _ => db_file_path = arg,
+        }
+    }
+    (db_file_path, config)
+}
+
+fn parse_flag_value(flag: &str, value: Option<String>) -> usize {
+    match value.as_deref().map(str::parse::<usize>) {
+        Some(Ok(parsed_value)) => parsed_value,
+        _ => {
+            eprintln!("Expected a number after '{}'.", flag);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn parse_journal_mode(value: Option<String>) -> JournalMode {
+    match value.as_deref().and_then(JournalMode::from_name) {
+        Some(journal_mode) => journal_mode,
+        None => {
+            eprintln!("Expected wal, journal or off after '--journal-mode'.");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn parse_synchronous(value: Option<String>) -> Synchronous {
+    match value.as_deref().and_then(Synchronous::from_name) {
+        Some(synchronous) => synchronous,
+        None => {
+            eprintln!("Expected off, normal or full after '--synchronous'.");
+            std::process::exit(1);
         }
     }
 }
@@ -67,11 +107,180 @@ fn do_meta_command(input: &str, table: &mut Table) -> MetaCommandResult {
     if input == ".exit" {
         table.db_close();
         std::process::exit(0);
+    } else if input == ".btree" {
+        match node::format_tree(table) {
+            Ok(tree) => print!("Tree:\n{}", tree),
+            Err(err) => println!("Error: {}.", err),
+        }
+        MetaCommandResult::MetaCommandSuccess
+    } else if input == ".check" {
+        do_check(table);
+        MetaCommandResult::MetaCommandSuccess
+    } else if input == ".constants" {
+        println!("Constants:");
+        print_constants(table.pager.page_size);
+        MetaCommandResult::MetaCommandSuccess
+    } else if input.split_whitespace().next() == Some(".dot") {
+        do_dot(input, table);
+        MetaCommandResult::MetaCommandSuccess
+    } else if input == ".freelist" {
+        print_free_list(table);
+        MetaCommandResult::MetaCommandSuccess
+    } else if input.split_whitespace().next() == Some(".journal_mode") {
+        do_journal_mode(input, table);
+        MetaCommandResult::MetaCommandSuccess
+    } else if input.split_whitespace().next() == Some(".load") {
+        do_load(input, table);
+        MetaCommandResult::MetaCommandSuccess
+    } else if input.split_whitespace().next() == Some(".synchronous") {
+        do_synchronous(input, table);
+        MetaCommandResult::MetaCommandSuccess
+    } else if input.split_whitespace().next() == Some(".vacuum") {
+        do_vacuum(input, table);
+        MetaCommandResult::MetaCommandSuccess
     } else {
         MetaCommandResult::MetaCommandUnrecognizedCommand
     }
 }
 
+fn do_check(table: &mut Table) {
+    let violations = check::check_table(table);
+    if violations.is_empty() {
+        println!("Integrity check passed.");
+        return;
+    }
+    println!("Integrity check found {} problems:", violations.len());
+    for violation in violations {
+        println!("  - {}", violation);
+    }
+}
+
+// node layout sizes, the capacities depend on the page size of the open file
+fn print_constants(page_size: usize) {
+    println!("PAGE_SIZE: {}", page_size);
+    println!("PAGE_CHECKSUM_SIZE: {}", PAGE_CHECKSUM_SIZE);
+    println!("ROW_MIN_SIZE: {}", ROW_MIN_SIZE);
+    println!("COMMON_NODE_HEADER_SIZE: {}", COMMON_NODE_HEADER_SIZE);
+    println!("LEAF_NODE_HEADER_SIZE: {}", LEAF_NODE_HEADER_SIZE);
+    println!("LEAF_NODE_SLOT_SIZE: {}", LEAF_NODE_SLOT_SIZE);
+    println!("LEAF_NODE_SPACE_FOR_CELLS: {}", leaf_node_space_for_cells(page_size));
+    println!("LEAF_NODE_MAX_CELL_SIZE: {}", leaf_node_max_cell_size(page_size));
+    println!("LEAF_NODE_MAX_LOCAL_PAYLOAD: {}", leaf_node_max_local_payload(page_size));
+    println!("LEAF_NODE_MAX_CELLS: {}", leaf_node_max_cells(page_size));
+    println!("INTERNAL_NODE_HEADER_SIZE: {}", INTERNAL_NODE_HEADER_SIZE);
+    println!("INTERNAL_NODE_CELL_SIZE: {}", INTERNAL_NODE_CELL_SIZE);
+    println!("INTERNAL_NODE_MAX_CELLS: {}", internal_node_max_cells(page_size));
+    println!("OVERFLOW_PAGE_CAPACITY: {}", overflow_page_capacity(page_size));
+    println!("HEADER_SIZE: {}", HEADER_SIZE);
+}
+
+// .dot <file> writes the tree as a Graphviz graph
+fn do_dot(input: &str, table: &mut Table) {
+    let file_name = match input.split_whitespace().nth(1) {
+        Some(file_name) => file_name,
+        None => {
+            eprintln!("Expected a file name after '.dot'.");
+            return;
+        }
+    };
+    let dot = match node::format_dot(table) {
+        Ok(dot) => dot,
+        Err(err) => {
+            println!("Error: {}.", err);
+            return;
+        }
+    };
+    match std::fs::write(file_name, dot) {
+        Ok(()) => println!("Wrote tree to '{}'.", file_name),
+        Err(err) => eprintln!("Error writing '{}': {}", file_name, err),
+    }
+}
+
+fn print_free_list(table: &mut Table) {
+    let free_list = match table.pager.free_list() {
+        Ok(free_list) => free_list,
+        Err(err) => {
+            println!("Error: {}.", err);
+            return;
+        }
+    };
+    println!("Free pages: {}", free_list.len());
+    for page_num in free_list {
+        println!("  - {}", page_num);
+    }
+}
+
+// .journal_mode [wal|journal|off] shows or changes how commits are made durable
+fn do_journal_mode(input: &str, table: &mut Table) {
+    if let Some(name) = input.split_whitespace().nth(1) {
+        let journal_mode = match JournalMode::from_name(name) {
+            Some(journal_mode) => journal_mode,
+            None => {
+                eprintln!("Unknown journal mode '{}', expected wal, journal or off.", name);
+                return;
+            }
+        };
+        if let Err(err) = table.set_journal_mode(journal_mode) {
+            eprintln!("Error changing journal mode: {}", err);
+            return;
+        }
+    }
+    println!("{}", table.pager.journal_mode().name());
+}
+
+// .load <file> [fill_percent] bulk loads rows sorted by id into an empty table
+fn do_load(input: &str, table: &mut Table) {
+    let parts: Vec<&str> = input.split_whitespace().skip(1).collect();
+    let (file_name, fill_percent) = match parts.as_slice() {
+        [file_name] => (file_name, DEFAULT_FILL_PERCENT),
+        [file_name, fill_percent] => match fill_percent.parse::<usize>() {
+            Ok(fill_percent) => (file_name, fill_percent),
+            Err(_) => {
+                eprintln!("Expected a fill percentage after the file name.");
+                return;
+            }
+        },
+        _ => {
+            eprintln!("Expected a file name after '.load'.");
+            return;
+        }
+    };
+    let result = bulk_load::read_rows(file_name).and_then(|rows| bulk_load::bulk_load(table, &rows, fill_percent));
+    match result {
+        Ok(rows_loaded) => println!("Loaded {} rows.", rows_loaded),
+        Err(err) => eprintln!("Error loading '{}': {}", file_name, err),
+    }
+}
+
+// .synchronous [off|normal|full] shows or changes how often data is synced to disk
+fn do_synchronous(input: &str, table: &mut Table) {
+    if let Some(name) = input.split_whitespace().nth(1) {
+        match Synchronous::from_name(name) {
+            Some(synchronous) => table.set_synchronous(synchronous),
+            None => {
+                eprintln!("Unknown synchronous level '{}', expected off, normal or full.", name);
+                return;
+            }
+        }
+    }
+    println!("{}", table.pager.synchronous().name());
+}
+
+// .vacuum [fill_percent]
+fn do_vacuum(input: &str, table: &mut Table) {
+    let fill_percent = match input.split_whitespace().nth(1).map(str::parse::<usize>) {
+        None => DEFAULT_FILL_PERCENT,
+        Some(Ok(fill_percent)) => fill_percent,
+        Some(Err(_)) => {
+            eprintln!("Expected a fill percentage after '.vacuum'.");
+            return;
+        }
+    };
+    match vacuum::vacuum(table, fill_percent) {
+        Ok(bytes_reclaimed) => println!("Vacuum reclaimed {} bytes.", bytes_reclaimed),
+        Err(err) => eprintln!("Error vacuuming: {}", err),
+    }
+}
 
 fn read_input() -> String {
     let mut input = String::new();
@@ -82,8 +291,13 @@ fn read_input() -> String {
     input.trim().to_string()
 }
 
-fn print_prompt() {
-    print!("db > ");
+fn print_prompt(in_transaction: bool) {
+    // the prompt shows when statements are waiting for a commit
+    if in_transaction {
+        print!("db (tx) > ");
+    } else {
+        print!("db > ");
+    }
     io::stdout().flush().unwrap_or_else(|err| {
         eprintln!("Error flushing stdout: {}", err);
         panic!("Error flushing stdout.");
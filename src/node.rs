@@ -1,6 +1,7 @@
-use crate::{constants::*, cursor};
+use crate::constants::*;
 use crate::cursor::Cursor;
-use crate::enums::NodeType;
+use crate::enums::{DbError, ExecuteResult, NodeType};
+use crate::overflow::{cell_payload_size, free_overflow_chain, leaf_cell};
 use crate::row::Row;
 use crate::table::Table;
 
@@ -16,34 +17,60 @@ fn set_leaf_node_num_cells(node: &mut [u8], num_cells: u32) {
     num_cells_slice.copy_from_slice(&num_cells.to_le_bytes());
 }
 
-pub fn leaf_node_cell_unmut(node: &[u8], cell_num: usize) -> &[u8] {
+pub fn leaf_node_cell_content_start(node: &[u8]) -> usize {
+    u16::from_le_bytes(
+        node[LEAF_NODE_CELL_CONTENT_OFFSET..LEAF_NODE_CELL_CONTENT_OFFSET + LEAF_NODE_CELL_CONTENT_SIZE]
+            .try_into()
+            .unwrap(),
+    ) as usize
+}
+
+fn set_leaf_node_cell_content_start(node: &mut [u8], offset: usize) {
+    node[LEAF_NODE_CELL_CONTENT_OFFSET..LEAF_NODE_CELL_CONTENT_OFFSET + LEAF_NODE_CELL_CONTENT_SIZE]
+        .copy_from_slice(&(offset as u16).to_le_bytes());
+}
+
+pub fn leaf_node_fragmented_bytes(node: &[u8]) -> usize {
+    u16::from_le_bytes(
+        node[LEAF_NODE_FRAGMENTED_BYTES_OFFSET..LEAF_NODE_FRAGMENTED_BYTES_OFFSET + LEAF_NODE_FRAGMENTED_BYTES_SIZE]
+            .try_into()
+            .unwrap(),
+    ) as usize
+}
+
+fn set_leaf_node_fragmented_bytes(node: &mut [u8], fragmented_bytes: usize) {
+    node[LEAF_NODE_FRAGMENTED_BYTES_OFFSET..LEAF_NODE_FRAGMENTED_BYTES_OFFSET + LEAF_NODE_FRAGMENTED_BYTES_SIZE]
+        .copy_from_slice(&(fragmented_bytes as u16).to_le_bytes());
+}
+
+/// Offset of a cell within the page, read from the cell pointer array.
+pub fn leaf_node_cell_offset(node: &[u8], cell_num: usize) -> usize {
+    let slot_offset = LEAF_NODE_HEADER_SIZE + cell_num * LEAF_NODE_SLOT_SIZE;
+    u16::from_le_bytes(node[slot_offset..slot_offset + LEAF_NODE_SLOT_SIZE].try_into().unwrap()) as usize
+}
+
+fn set_leaf_node_cell_offset(node: &mut [u8], cell_num: usize, cell_offset: usize) {
+    let slot_offset = LEAF_NODE_HEADER_SIZE + cell_num * LEAF_NODE_SLOT_SIZE;
+    node[slot_offset..slot_offset + LEAF_NODE_SLOT_SIZE].copy_from_slice(&(cell_offset as u16).to_le_bytes());
+}
+
+fn leaf_node_cell_range(node: &[u8], cell_num: usize) -> std::ops::Range<usize> {
     let num_cells = leaf_node_num_cells(node);
-    if cell_num as u32 > num_cells {
-        eprintln!("Tried to access cell {} > {}.", cell_num, num_cells);
+    if cell_num as u32 >= num_cells {
+        eprintln!("Tried to access cell {} >= {}.", cell_num, num_cells);
         panic!("Tried to access cell out of bounds.");
     }
-    let cell_offset = LEAF_NODE_HEADER_SIZE + cell_num * LEAF_NODE_CELL_SIZE;
-    &node[cell_offset..cell_offset + LEAF_NODE_CELL_SIZE]
+    let cell_offset = leaf_node_cell_offset(node, cell_num);
+    cell_offset..cell_offset + leaf_node_cell_size(cell_payload_size(&node[cell_offset..]), node.len())
 }
 
 pub fn leaf_node_cell(node: &[u8], cell_num: usize) -> &[u8] {
-    let num_cells = leaf_node_num_cells(node);
-    if cell_num as u32 > num_cells {
-        eprintln!("Tried to access cell {} > {}.", cell_num, num_cells);
-        panic!("Tried to access cell out of bounds.");
-    }
-    let cell_offset = LEAF_NODE_HEADER_SIZE + cell_num * LEAF_NODE_CELL_SIZE;
-    &node[cell_offset..cell_offset + LEAF_NODE_CELL_SIZE]
+    &node[leaf_node_cell_range(node, cell_num)]
 }
 
 pub fn leaf_node_cell_mut(node: &mut [u8], cell_num: usize) -> &mut [u8] {
-    let num_cells = leaf_node_num_cells(node);
-    if cell_num as u32 > num_cells {
-        eprintln!("Tried to access cell {} > {}.", cell_num, num_cells);
-        panic!("Tried to access cell out of bounds.");
-    }
-    let cell_offset = LEAF_NODE_HEADER_SIZE + cell_num * LEAF_NODE_CELL_SIZE;
-    node[cell_offset..cell_offset + LEAF_NODE_CELL_SIZE].as_mut()
+    let cell_range = leaf_node_cell_range(node, cell_num);
+    &mut node[cell_range]
 }
 
 pub fn leaf_node_key(node: &[u8], cell_num: usize) -> &[u8] {
@@ -51,16 +78,77 @@ pub fn leaf_node_key(node: &[u8], cell_num: usize) -> &[u8] {
     &cell[LEAF_NODE_KEY_OFFSET..LEAF_NODE_KEY_OFFSET + LEAF_NODE_KEY_SIZE]
 }
 
-pub fn leaf_node_value(node: &mut [u8], cell_num: usize) -> &mut [u8] {
-    let cell = leaf_node_cell_mut(node, cell_num);
-    cell[LEAF_NODE_KEY_SIZE..LEAF_NODE_KEY_SIZE + LEAF_NODE_VALUE_SIZE].as_mut()
+/// Bytes free for new cells and their slots, counting fragmented bytes that a
+/// defragmentation would win back.
+pub fn leaf_node_free_space(node: &[u8]) -> usize {
+    let slots_end = LEAF_NODE_HEADER_SIZE + leaf_node_num_cells(node) as usize * LEAF_NODE_SLOT_SIZE;
+    leaf_node_cell_content_start(node) - slots_end + leaf_node_fragmented_bytes(node)
+}
+
+/// Bytes taken by the cells and their slots.
+pub fn leaf_node_used_space(node: &[u8]) -> usize {
+    leaf_node_space_for_cells(node.len()) - leaf_node_free_space(node)
+}
+
+// Space a cell takes in a leaf, including its slot.
+fn cell_footprint(cell: &[u8]) -> usize {
+    cell.len() + LEAF_NODE_SLOT_SIZE
+}
+
+/// Inserts a cell at `cell_num`, shifting the slots after it. Defragments the
+/// page first when the gap between the slots and the cells is too small. The
+/// caller checks that the cell fits.
+pub fn leaf_node_insert_cell(node: &mut [u8], cell_num: usize, cell: &[u8]) {
+    let num_cells = leaf_node_num_cells(node) as usize;
+    let slots_end = LEAF_NODE_HEADER_SIZE + num_cells * LEAF_NODE_SLOT_SIZE;
+    if leaf_node_cell_content_start(node) - slots_end < cell_footprint(cell) {
+        defragment_leaf_node(node);
+    }
+    let cell_offset = leaf_node_cell_content_start(node) - cell.len();
+    node[cell_offset..cell_offset + cell.len()].copy_from_slice(cell);
+    set_leaf_node_cell_content_start(node, cell_offset);
+    let slot_offset = LEAF_NODE_HEADER_SIZE + cell_num * LEAF_NODE_SLOT_SIZE;
+    node.copy_within(slot_offset..slots_end, slot_offset + LEAF_NODE_SLOT_SIZE);
+    set_leaf_node_cell_offset(node, cell_num, cell_offset);
+    set_leaf_node_num_cells(node, num_cells as u32 + 1);
+}
+
+/// Removes the cell at `cell_num`. Its bytes count as fragmented unless it was
+/// the first cell of the content area.
+pub fn leaf_node_remove_cell(node: &mut [u8], cell_num: usize) {
+    let cell_range = leaf_node_cell_range(node, cell_num);
+    if cell_range.start == leaf_node_cell_content_start(node) {
+        set_leaf_node_cell_content_start(node, cell_range.end);
+    } else {
+        let fragmented_bytes = leaf_node_fragmented_bytes(node) + cell_range.len();
+        set_leaf_node_fragmented_bytes(node, fragmented_bytes);
+    }
+    let num_cells = leaf_node_num_cells(node) as usize;
+    let slot_offset = LEAF_NODE_HEADER_SIZE + cell_num * LEAF_NODE_SLOT_SIZE;
+    let slots_end = LEAF_NODE_HEADER_SIZE + num_cells * LEAF_NODE_SLOT_SIZE;
+    node.copy_within(slot_offset + LEAF_NODE_SLOT_SIZE..slots_end, slot_offset);
+    set_leaf_node_num_cells(node, num_cells as u32 - 1);
+    if num_cells == 1 {
+        // nothing is left to fragment the content area
+        set_leaf_node_cell_content_start(node, usable_page_size(node.len()));
+        set_leaf_node_fragmented_bytes(node, 0);
+    }
+}
+
+/// Packs the cells against the end of the page so all free space is in one gap.
+pub fn defragment_leaf_node(node: &mut [u8]) {
+    let cells = leaf_node_cells(node);
+    write_leaf_node_cells(node, &cells);
 }
 
 pub fn initialize_leaf_node(node: &mut [u8]) {
     set_leaf_node_num_cells(node, 0);
+    set_leaf_node_cell_content_start(node, usable_page_size(node.len()));
+    set_leaf_node_fragmented_bytes(node, 0);
     set_node_type(node, NodeType::NodeLeaf);
     set_node_root(node, false);
     set_next_leaf(node, 0);
+    set_prev_leaf(node, 0);
 }
 
 pub fn initialize_internal_node(node: &mut [u8]) {
@@ -70,36 +158,31 @@ pub fn initialize_internal_node(node: &mut [u8]) {
     set_internal_node_right_child(node, INVALID_PAGE_NUMBER);
 }
 
-pub fn leaf_node_insert(cursor: &mut Cursor, key: u32, row_to_insert: &Row) {
-    let node = cursor.table.pager.get_page(cursor.page_num);
-    let num_cells = leaf_node_num_cells(node);
-    if num_cells >= LEAF_NODE_MAX_CELLS as u32 {
-        leaf_node_split_and_insert(cursor, key, row_to_insert);
-        return;
-    }
-    if cursor.cell_num < num_cells as usize {
-        // make room for the new cell
-        for i in (cursor.cell_num..num_cells as usize).rev() {
-            let destination_offset = LEAF_NODE_HEADER_SIZE + (i + 1) * LEAF_NODE_CELL_SIZE;
-            let source_offset = LEAF_NODE_HEADER_SIZE + i * LEAF_NODE_CELL_SIZE;
-            let cloned_slice = node[source_offset..source_offset + LEAF_NODE_CELL_SIZE].to_vec();
-            node[destination_offset..destination_offset + LEAF_NODE_CELL_SIZE]
-                .copy_from_slice(&cloned_slice);
-        }
+pub fn leaf_node_insert(cursor: &mut Cursor, key: u32, row_to_insert: &Row) -> Result<ExecuteResult, DbError> {
+    // checked before anything is written so a duplicate leaves the tree as it was,
+    // whether or not the leaf would have been split
+    if cursor.cursor_key()? == Some(key) {
+        return Ok(ExecuteResult::ExecuteTableDuplicateKey);
+    }
+    let cell = leaf_cell(&mut cursor.table.pager, &row_to_insert.serialize_row())?;
+    let node = cursor.table.pager.get_page(cursor.page_num)?;
+    if leaf_node_free_space(node) < cell_footprint(&cell) {
+        leaf_node_split_and_insert(cursor, &cell)?;
+        return Ok(ExecuteResult::ExecuteSuccess);
     }
-    // insert the new cell
-    set_leaf_node_num_cells(node, num_cells + 1);
-    let destination_cell = leaf_node_cell_mut(node, cursor.cell_num);
-    row_to_insert.serialize_row(destination_cell);
+    leaf_node_insert_cell(node, cursor.cell_num, &cell);
+    Ok(ExecuteResult::ExecuteSuccess)
 }
 
-pub fn get_node_type(node: &[u8]) -> NodeType {
+/// Type of the node read from `page_num`. The tree only links to nodes, so any
+/// other type byte is reported as `DbError::CorruptPage`.
+pub fn get_node_type(node: &[u8], page_num: usize) -> Result<NodeType, DbError> {
     let node_type_slice = &node[NODE_TYPE_OFFSET..NODE_TYPE_OFFSET + NODE_TYPE_SIZE];
     let node_type = u8::from_le_bytes(node_type_slice.try_into().unwrap());
     match node_type {
-        0 => NodeType::NodeInternal,
-        1 => NodeType::NodeLeaf,
-        _ => panic!("Unknown node type."),
+        0 => Ok(NodeType::NodeInternal),
+        1 => Ok(NodeType::NodeLeaf),
+        _ => Err(DbError::CorruptPage(page_num)),
     }
 }
 
@@ -111,72 +194,51 @@ pub fn set_node_type(node: &mut [u8], node_type: NodeType) {
     }
 }
 
-pub fn leaf_node_split_and_insert(cursor: &mut Cursor, _key: u32, row_to_insert: &Row) {
-    let new_page_num = cursor.table.pager.get_unused_page_num();
-    let new_node_buffer = &mut [b'\0'; PAGE_SIZE][..];
-    let old_node = cursor.table.pager.get_page_unmut(cursor.page_num);
+pub fn leaf_node_split_and_insert(cursor: &mut Cursor, cell: &[u8]) -> Result<(), DbError> {
+    let page_size = cursor.table.pager.page_size;
+    let new_page_num = cursor.table.pager.get_unused_page_num()?;
+    let new_node_buffer = &mut vec![b'\0'; page_size][..];
+    initialize_leaf_node(new_node_buffer);
 
     // copy old node to a temporary buffer
-    let old_node_buffer = &mut [b'\0'; PAGE_SIZE];
-    old_node_buffer.copy_from_slice(old_node);
-
-    let old_max_key = get_node_max_key(cursor.table, old_node_buffer);
-
-    // All existing keys plus new key should be divided
-    // evenly between old (left) and new (right) nodes.
-    // Starting from the right, move each key to correct position.
-    for i in (0..LEAF_NODE_MAX_CELLS + 1).rev() {
-        let destination_node: &mut [u8];
-        if i >= LEAF_NODE_LEFT_SPLIT_COUNT {
-            destination_node = new_node_buffer;
-        } else {
-            destination_node = old_node_buffer;
-        }
-        let index_within_node = i % LEAF_NODE_LEFT_SPLIT_COUNT;
-        let destination = leaf_node_cell_mut(destination_node, index_within_node);
-        if i == cursor.cell_num {
-            // copy key and value to the new node
-            let destination_cell = leaf_node_cell_mut(destination_node, index_within_node);
-            row_to_insert.serialize_row(destination_cell);
-        } else if i > cursor.cell_num {
-            let source = leaf_node_cell_unmut(old_node, i - 1);
-            destination.copy_from_slice(source);
-        } else {
-            let source = leaf_node_cell_unmut(old_node, i);
-            destination.copy_from_slice(source);
-        }
-    }
+    let old_node_buffer = &mut vec![b'\0'; page_size];
+    old_node_buffer.copy_from_slice(cursor.table.pager.get_page_ref(cursor.page_num)?);
+
+    let old_max_key = get_node_max_key(cursor.table, cursor.page_num, old_node_buffer)?;
+
+    // All existing cells plus the new one are divided between old (left) and
+    // new (right) nodes so that each half takes about the same number of bytes.
+    let mut cells = leaf_node_cells(old_node_buffer);
+    cells.insert(cursor.cell_num, cell.to_vec());
+    let right_cells = cells.split_off(leaf_node_split_index(&cells));
+    write_leaf_node_cells(new_node_buffer, &right_cells);
+    write_leaf_node_cells(old_node_buffer, &cells);
 
     set_node_parent(new_node_buffer, get_node_parent(old_node_buffer));
-    set_next_leaf(new_node_buffer, leaf_node_next_leaf(old_node_buffer));
+    let old_next_leaf = leaf_node_next_leaf(old_node_buffer);
+    set_next_leaf(new_node_buffer, old_next_leaf);
+    set_prev_leaf(new_node_buffer, cursor.page_num as u32);
     set_next_leaf(old_node_buffer, new_page_num as u32);
+    if old_next_leaf != 0 {
+        set_prev_leaf(cursor.table.pager.get_page(old_next_leaf as usize)?, new_page_num as u32);
+    }
 
-    let new_node = cursor.table.pager.get_page(new_page_num);
+    let new_node = cursor.table.pager.get_page(new_page_num)?;
     // copy new_node_buffer to new_node
     new_node.copy_from_slice(new_node_buffer);
 
-    initialize_leaf_node(new_node);
-
-    // Update cell count on both leaf nodes
-    let old_node_num_cells = LEAF_NODE_LEFT_SPLIT_COUNT as u32;
-    let new_node_num_cells = LEAF_NODE_RIGHT_SPLIT_COUNT as u32;
-
-    set_leaf_node_num_cells(new_node, new_node_num_cells);
-    set_leaf_node_num_cells(old_node_buffer, old_node_num_cells);
-
-    let old_node = cursor.table.pager.get_page(cursor.page_num);
+    let old_node = cursor.table.pager.get_page(cursor.page_num)?;
 
     // copy old_node_buffer to old_node
     old_node.copy_from_slice(old_node_buffer);
 
-    if is_node_root(old_node) {
-        return create_new_root(cursor.table, new_page_num);
+    if is_node_root(old_node, cursor.page_num)? {
+        create_new_root(cursor.table, new_page_num)?;
     } else {
         let parent_page_num = get_node_parent(old_node);
-        let new_max_key = get_node_max_key(cursor.table, old_node_buffer);
-        let parent_node = cursor.table.pager.get_page(parent_page_num as usize);
-        let parent_node_buffer = &mut [b'\0'; PAGE_SIZE];
-        parent_node_buffer.copy_from_slice(parent_node);
+        let new_max_key = get_node_max_key(cursor.table, cursor.page_num, old_node_buffer)?;
+        let parent_node_buffer = &mut vec![b'\0'; page_size];
+        parent_node_buffer.copy_from_slice(cursor.table.pager.get_page_ref(parent_page_num as usize)?);
 
         let num_keys = internal_node_num_keys(parent_node_buffer);
 
@@ -184,19 +246,23 @@ pub fn leaf_node_split_and_insert(cursor: &mut Cursor, _key: u32, row_to_insert:
             cursor
                 .table
                 .internal_node_find_child(parent_node_buffer, old_max_key, num_keys);
-        let parent_node = cursor.table.pager.get_page(parent_page_num as usize);
-        update_internal_node_key(parent_node, new_max_key, old_child_num);
-        internal_node_insert(cursor.table, parent_page_num as usize, new_page_num);
+        // a leaf that is its parent's right child has no key to update
+        if old_child_num < num_keys as usize {
+            let parent_node = cursor.table.pager.get_page(parent_page_num as usize)?;
+            update_internal_node_key(parent_node, new_max_key, old_child_num);
+        }
+        internal_node_insert(cursor.table, parent_page_num as usize, new_page_num)?;
     }
+    Ok(())
 }
 
-fn is_node_root(node: &[u8]) -> bool {
+fn is_node_root(node: &[u8], page_num: usize) -> Result<bool, DbError> {
     let is_root_slice = &node[IS_ROOT_OFFSET..IS_ROOT_OFFSET + IS_ROOT_SIZE];
     let is_root = u8::from_le_bytes(is_root_slice.try_into().unwrap());
     match is_root {
-        0 => false,
-        1 => true,
-        _ => panic!("Unknown value for is_root."),
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(DbError::CorruptPage(page_num)),
     }
 }
 
@@ -208,29 +274,35 @@ pub fn set_node_root(node: &mut [u8], is_root: bool) {
     }
 }
 
-fn set_next_leaf(node: &mut [u8], next_leaf: u32) {
+pub fn set_next_leaf(node: &mut [u8], next_leaf: u32) {
     let next_leaf_slice = &mut node
         [LEAF_NODE_NEXT_LEAF_OFFSET..LEAF_NODE_NEXT_LEAF_OFFSET + LEAF_NODE_NEXT_LEAF_SIZE];
     next_leaf_slice.copy_from_slice(&next_leaf.to_le_bytes());
 }
 
-fn create_new_root(table: &mut Table, right_child_page_num: usize) {
+pub fn set_prev_leaf(node: &mut [u8], prev_leaf: u32) {
+    node[LEAF_NODE_PREV_LEAF_OFFSET..LEAF_NODE_PREV_LEAF_OFFSET + LEAF_NODE_PREV_LEAF_SIZE]
+        .copy_from_slice(&prev_leaf.to_le_bytes());
+}
+
+fn create_new_root(table: &mut Table, right_child_page_num: usize) -> Result<(), DbError> {
+    let page_size = table.pager.page_size;
     // populating the right child page in the pager
-    let _right_child = table.pager.get_page(right_child_page_num);
+    let _right_child = table.pager.get_page(right_child_page_num)?;
     
-    let left_child_page_num = table.pager.get_unused_page_num();
+    let left_child_page_num = table.pager.get_unused_page_num()?;
 
-    let root = table.pager.get_page(table.root_page_num);
+    let root = table.pager.get_page_ref(table.root_page_num)?;
 
     // create a buffer to hold the old root node
-    let new_root_buffer = &mut [b'\0'; PAGE_SIZE];
+    let new_root_buffer = &mut vec![b'\0'; page_size];
     new_root_buffer.copy_from_slice(root);
 
-    let new_left_child_buffer = &mut [b'\0'; PAGE_SIZE];
+    let new_left_child_buffer = &mut vec![b'\0'; page_size];
 
-    match get_node_type(&root) {
+    match get_node_type(root, table.root_page_num)? {
         NodeType::NodeInternal => {
-            initialize_internal_node(table.pager.get_page(right_child_page_num));
+            initialize_internal_node(table.pager.get_page(right_child_page_num)?);
             initialize_internal_node(new_left_child_buffer);
         }
         NodeType::NodeLeaf => (),
@@ -239,22 +311,23 @@ fn create_new_root(table: &mut Table, right_child_page_num: usize) {
     new_left_child_buffer.copy_from_slice(new_root_buffer);
     set_node_root(new_left_child_buffer, false);
 
-    match get_node_type(new_left_child_buffer) {
+    match get_node_type(new_left_child_buffer, table.root_page_num)? {
         NodeType::NodeInternal => {
             let mut child_node: &mut [u8];
             let left_child_num_keys = internal_node_num_keys(new_left_child_buffer);
             for i in 0..left_child_num_keys {
                 child_node = table.pager.get_page(
-                    internal_node_child_page_num(new_left_child_buffer, i as usize));
+                    internal_node_child_page_num(new_left_child_buffer, i as usize))?;
                 set_node_parent(child_node, left_child_page_num as u32);
             }
 
             child_node = table.pager.get_page(
                 usize::from_le_bytes(internal_node_right_child(new_left_child_buffer).try_into().unwrap()),
-            );
+            )?;
             set_node_parent(child_node, left_child_page_num as u32);
         }
-        NodeType::NodeLeaf => (),
+        // the right leaf was linked back to the root page the left leaf moved out of
+        NodeType::NodeLeaf => set_prev_leaf(table.pager.get_page(right_child_page_num)?, left_child_page_num as u32),
     }
 
     initialize_internal_node(new_root_buffer);
@@ -263,34 +336,36 @@ fn create_new_root(table: &mut Table, right_child_page_num: usize) {
     
     set_internal_node_num_keys(new_root_buffer, num_keys_root_value);
     set_internal_node_child(new_root_buffer, left_child_page_num, 0);
-    let left_child_max_key = get_node_max_key(table, new_left_child_buffer);
+    // the buffer still holds the node read from the root page
+    let left_child_max_key = get_node_max_key(table, table.root_page_num, new_left_child_buffer)?;
     set_internal_node_key(new_root_buffer, left_child_max_key, 0);
     set_internal_node_right_child(new_root_buffer, right_child_page_num);
 
     // copy the buffers back to the pages
-    let root = table.pager.get_page(table.root_page_num);
+    let root = table.pager.get_page(table.root_page_num)?;
     root.copy_from_slice(new_root_buffer);
 
-    let left_child = table.pager.get_page(left_child_page_num);
+    let left_child = table.pager.get_page(left_child_page_num)?;
     left_child.copy_from_slice(new_left_child_buffer);
     set_node_parent(left_child, table.root_page_num as u32);
-    let right_child = table.pager.get_page(right_child_page_num);
+    let right_child = table.pager.get_page(right_child_page_num)?;
     set_node_parent(right_child, table.root_page_num as u32);
+    Ok(())
 }
 
-fn internal_node_insert(table: &mut Table, parent_page_num: usize, child_page_num: usize) {
-    let parent = table.pager.get_page_unmut(parent_page_num);
-    let parent_node_num_keys = internal_node_num_keys(parent);
-    let parent_node_buffer = &mut [b'\0'; PAGE_SIZE];
-    parent_node_buffer.copy_from_slice(parent);
-    let child = table.pager.get_page_unmut(child_page_num);
-    let child_max_key = get_node_max_key(table, child);
+fn internal_node_insert(table: &mut Table, parent_page_num: usize, child_page_num: usize) -> Result<(), DbError> {
+    let page_size = table.pager.page_size;
+    let parent_node_buffer = &mut vec![b'\0'; page_size];
+    parent_node_buffer.copy_from_slice(table.pager.get_page_ref(parent_page_num)?);
+    let parent_node_num_keys = internal_node_num_keys(parent_node_buffer);
+    let child_node_buffer = &mut vec![b'\0'; page_size];
+    child_node_buffer.copy_from_slice(table.pager.get_page_ref(child_page_num)?);
+    let child_max_key = get_node_max_key(table, child_page_num, child_node_buffer)?;
     let index =
         table.internal_node_find_child(parent_node_buffer, child_max_key, parent_node_num_keys);
 
-    if parent_node_num_keys >= INTERNAL_NODE_MAX_CELLS as u32 {
-        internal_node_split_and_insert(table, parent_page_num, child_page_num);
-        return;
+    if parent_node_num_keys >= internal_node_max_cells(page_size) as u32 {
+        return internal_node_split_and_insert(table, parent_page_num, child_page_num);
     }
 
     let right_child_page_num = usize::from_le_bytes(
@@ -302,25 +377,21 @@ fn internal_node_insert(table: &mut Table, parent_page_num: usize, child_page_nu
     if right_child_page_num == INVALID_PAGE_NUMBER {
         set_internal_node_right_child(parent_node_buffer, child_page_num);
         // copy the buffer back to the page
-        let parent = table.pager.get_page(parent_page_num);
+        let parent = table.pager.get_page(parent_page_num)?;
         parent.copy_from_slice(parent_node_buffer);
-        return;
+        return Ok(());
     }
 
-    let right_child = table.pager.get_page(right_child_page_num);
+    let right_child_buffer = &mut vec![b'\0'; page_size];
+    right_child_buffer.copy_from_slice(table.pager.get_page_ref(right_child_page_num)?);
+    let right_child_max_key = get_node_max_key(table, right_child_page_num, right_child_buffer)?;
 
-    // if we are already at the maximum number of cells, we can't increment before splitting.
-    // Incrementing without inserting a new key/child pair and immediately splitting has the effect of
-    // creating a new key at max_cells + 1, with an uninitialized value.
     increment_internal_node_num_keys(parent_node_buffer);
 
-    let right_child_buffer = &mut [b'\0'; PAGE_SIZE];
-    right_child_buffer.copy_from_slice(right_child);  
-
-    if child_max_key > get_node_max_key(table, right_child_buffer) {
+    if child_max_key > right_child_max_key {
         // Replace right child
-        set_internal_node_child(parent_node_buffer, right_child_page_num, parent_node_num_keys as usize,);
-        set_internal_node_key(parent_node_buffer, get_node_max_key(table, right_child_buffer), parent_node_num_keys as usize);
+        set_internal_node_child(parent_node_buffer, right_child_page_num, parent_node_num_keys as usize);
+        set_internal_node_key(parent_node_buffer, right_child_max_key, parent_node_num_keys as usize);
         set_internal_node_right_child(parent_node_buffer, child_page_num);
     } else {
         // Make room for the new cell
@@ -337,129 +408,341 @@ fn internal_node_insert(table: &mut Table, parent_page_num: usize, child_page_nu
     }
 
     // copy the buffer back to the page
-    let parent = table.pager.get_page(parent_page_num);
+    let parent = table.pager.get_page(parent_page_num)?;
     parent.copy_from_slice(parent_node_buffer);
-
-    let right_child = table.pager.get_page(right_child_page_num);
-    right_child.copy_from_slice(right_child_buffer);
+    Ok(())
 }
 
 fn internal_node_split_and_insert(
     table: &mut Table,
     parent_page_num: usize,
     child_page_num: usize,
-) {
+) -> Result<(), DbError> {
+    let page_size = table.pager.page_size;
     let mut old_page_num = parent_page_num;
-    let mut old_node = table.pager.get_page_unmut(old_page_num);
-    let old_max = get_node_max_key(table, old_node);
+    let old_node_buffer = &mut vec![b'\0'; page_size];
+    old_node_buffer.copy_from_slice(table.pager.get_page_ref(old_page_num)?);
+    let old_max = get_node_max_key(table, old_page_num, old_node_buffer)?;
+
+    let child_node_buffer = &mut vec![b'\0'; page_size];
+    child_node_buffer.copy_from_slice(table.pager.get_page_ref(child_page_num)?);
+    let child_max = get_node_max_key(table, child_page_num, child_node_buffer)?;
+
+    // Gather every child of the full node plus the new one in key order,
+    // then give the lower half to the old node and the upper half to the new node.
+    let mut left_entries = internal_node_entries(table, old_node_buffer)?;
+    let index = left_entries.partition_point(|&(_, key)| key < child_max);
+    left_entries.insert(index, (child_page_num, child_max));
+    let right_entries = left_entries.split_off(left_entries.len().div_ceil(2));
+    let left_max = left_entries[left_entries.len() - 1].1;
+
+    let new_page_num = table.pager.get_unused_page_num()?;
+    let splitting_root = is_node_root(old_node_buffer, old_page_num)?;
+    if splitting_root {
+        // the root keeps its page, its contents move to a new left child
+        create_new_root(table, new_page_num)?;
+        old_page_num = internal_node_child_page_num(table.pager.get_page_ref(table.root_page_num)?, 0);
+        old_node_buffer.copy_from_slice(table.pager.get_page_ref(old_page_num)?);
+    } else {
+        initialize_internal_node(table.pager.get_page(new_page_num)?);
+    }
+    let parent_page_num = get_node_parent(old_node_buffer) as usize;
 
-    let old_node_buffer = &mut [b'\0'; PAGE_SIZE];
-    old_node_buffer.copy_from_slice(old_node);
+    let new_node_buffer = &mut vec![b'\0'; page_size];
+    new_node_buffer.copy_from_slice(table.pager.get_page_ref(new_page_num)?);
+    write_internal_node_entries(new_node_buffer, &right_entries);
+    set_node_parent(new_node_buffer, parent_page_num as u32);
+    table.pager.get_page(new_page_num)?.copy_from_slice(new_node_buffer);
 
-    let child_node = table.pager.get_page_unmut(child_page_num);
-    let child_max = get_node_max_key(table, child_node);
+    write_internal_node_entries(old_node_buffer, &left_entries);
+    table.pager.get_page(old_page_num)?.copy_from_slice(old_node_buffer);
 
-    let new_page_num = table.pager.get_unused_page_num();
+    for &(page_num, _) in &left_entries {
+        set_node_parent(table.pager.get_page(page_num)?, old_page_num as u32);
+    }
+    for &(page_num, _) in &right_entries {
+        set_node_parent(table.pager.get_page(page_num)?, new_page_num as u32);
+    }
 
-    let splitting_root = is_node_root(old_node_buffer);
+    // The old node now ends at the middle key, so its key in the parent shrinks
+    let parent_node = table.pager.get_page_ref(parent_page_num)?;
+    let num_keys = internal_node_num_keys(parent_node);
+    let parent_node_buffer = parent_node.to_vec();
+    let old_child_num = table.internal_node_find_child(&parent_node_buffer, old_max, num_keys);
+    if old_child_num < num_keys as usize {
+        update_internal_node_key(table.pager.get_page(parent_page_num)?, left_max, old_child_num);
+    }
 
-    let parent_node: &mut [u8];
-    let parent_node_buffer = &mut [b'\0'; PAGE_SIZE];
-    let new_node: &mut [u8];
-    let new_node_buffer = &mut [b'\0'; PAGE_SIZE];
-    if splitting_root {
-        create_new_root(table, new_page_num);
-        new_node_buffer.copy_from_slice(table.pager.get_page(new_page_num));
-        parent_node = table.pager.get_page(table.root_page_num);
-        parent_node_buffer.copy_from_slice(parent_node);
-        old_page_num =
-            usize::from_le_bytes(internal_node_child(parent_node, 0).try_into().unwrap());
-        old_node = table.pager.get_page(old_page_num);
-        old_node_buffer.copy_from_slice(old_node);
-    } else {
-        let old_node_parent = get_node_parent(old_node);
-        parent_node = table.pager.get_page(old_node_parent as usize);
-        parent_node_buffer.copy_from_slice(parent_node);
-        new_node = table.pager.get_page(new_page_num);
-        initialize_internal_node(new_node);
-        new_node_buffer.copy_from_slice(new_node);
+    if !splitting_root {
+        internal_node_insert(table, parent_page_num, new_page_num)?;
     }
+    Ok(())
+}
+
+// Lists the children of an internal node with the max key of each one.
+// The right child has no key of its own, so its max key is looked up in its subtree.
+fn internal_node_entries(table: &mut Table, node: &[u8]) -> Result<Vec<(usize, u32)>, DbError> {
+    let num_keys = internal_node_num_keys(node) as usize;
+    let mut entries: Vec<(usize, u32)> = (0..num_keys)
+        .map(|i| {
+            let key = u32::from_le_bytes(internal_node_key(node, i).try_into().unwrap());
+            (internal_node_child_page_num(node, i), key)
+        })
+        .collect();
+    let right_child_page_num =
+        usize::from_le_bytes(internal_node_right_child_unmut(node).try_into().unwrap());
+    let right_child_buffer = table.pager.get_page_ref(right_child_page_num)?.to_vec();
+    entries.push((right_child_page_num, get_node_max_key(table, right_child_page_num, &right_child_buffer)?));
+    Ok(entries)
+}
+
+// Lays out the entries as cells, the last entry becomes the right child.
+fn write_internal_node_entries(node: &mut [u8], entries: &[(usize, u32)]) {
+    let children: Vec<usize> = entries.iter().map(|&(child_page_num, _)| child_page_num).collect();
+    let keys: Vec<u32> = entries[..entries.len() - 1].iter().map(|&(_, key)| key).collect();
+    write_internal_node(node, &children, &keys);
+}
+
+// Child page numbers of an internal node, ending with the right child.
+pub fn internal_node_children(node: &[u8]) -> Vec<usize> {
+    let num_keys = internal_node_num_keys(node) as usize;
+    let mut children: Vec<usize> = (0..num_keys).map(|i| internal_node_child_page_num(node, i)).collect();
+    children.push(usize::from_le_bytes(internal_node_right_child_unmut(node).try_into().unwrap()));
+    children
+}
+
+pub fn internal_node_keys(node: &[u8]) -> Vec<u32> {
+    let num_keys = internal_node_num_keys(node) as usize;
+    (0..num_keys)
+        .map(|i| u32::from_le_bytes(internal_node_key(node, i).try_into().unwrap()))
+        .collect()
+}
+
+// Writes the children and keys of an internal node, `children` has one more
+// entry than `keys` and its last entry becomes the right child.
+pub fn write_internal_node(node: &mut [u8], children: &[usize], keys: &[u32]) {
+    set_internal_node_num_keys(node, keys.len() as u32);
+    for (i, &key) in keys.iter().enumerate() {
+        set_internal_node_child(node, children[i], i);
+        set_internal_node_key(node, key, i);
+    }
+    set_internal_node_right_child(node, children[keys.len()]);
+}
 
-    let mut cur_page_num = usize::from_le_bytes(
-        internal_node_right_child_unmut(old_node_buffer)
-            .try_into()
-            .unwrap(),
-    );
-    // First put the right child in the new node and set right child to invalid
-    internal_node_insert(table, new_page_num, cur_page_num);
-    new_node_buffer.copy_from_slice(table.pager.get_page(new_page_num));
-
-    let mut cur = table.pager.get_page(cur_page_num);
-    set_node_parent(cur, new_page_num as u32);
-    let cur_node_buffer = &mut [b'\0'; PAGE_SIZE];
-    cur_node_buffer.copy_from_slice(cur);
-    set_internal_node_right_child(old_node_buffer, INVALID_PAGE_NUMBER);
-
-    // For each key until you get the middle key, move the key and child to the new node
-    for i in ((INTERNAL_NODE_MAX_CELLS / 2 + 1)..(INTERNAL_NODE_MAX_CELLS)).rev() {
-        cur_page_num = usize::from_le_bytes(internal_node_child(old_node_buffer, i).try_into().unwrap());
-        internal_node_insert(table, new_page_num, cur_page_num);
-        cur = table.pager.get_page(cur_page_num);
-        cur_node_buffer.copy_from_slice(cur);
-        set_node_parent(cur_node_buffer, new_page_num as u32);
-        decrement_internal_node_num_keys(old_node_buffer);
-        let cur = table.pager.get_page(cur_page_num);
-        cur.copy_from_slice(cur_node_buffer);
-    }
-
-    new_node_buffer.copy_from_slice(table.pager.get_page(new_page_num));
-
-    // Set child before middle key which is now the highest key to be node's right child
-    // and decrement the number of keys
-    let old_num_keys = internal_node_num_keys(old_node_buffer);
-    let old_node_child = internal_node_child(old_node_buffer, (old_num_keys - 1) as usize);
-    let old_node_child_num = usize::from_le_bytes(old_node_child.try_into().unwrap());
-
-    set_internal_node_right_child(old_node_buffer, old_node_child_num);
-
-    decrement_internal_node_num_keys(old_node_buffer);
-
-    // Determine which of the two nodes to insert into
-    let max_after_split = get_node_max_key(table, old_node_buffer);
-    let destination_page_num = if child_max < max_after_split {
-        old_page_num
-    } else {
-        new_page_num
-    };
-    internal_node_insert(table, destination_page_num, child_page_num);
-    new_node_buffer.copy_from_slice(table.pager.get_page(new_page_num));
-    let child_node = table.pager.get_page(child_page_num);
-    set_node_parent(child_node, destination_page_num as u32);
-
-    let old_num_keys = internal_node_num_keys(old_node_buffer);
-    let old_child_num = table.internal_node_find_child(parent_node_buffer, old_max, old_num_keys);
-
-    update_internal_node_key(
-        parent_node_buffer,
-        get_node_max_key(table, old_node_buffer),
-        old_child_num,
-    );
+// A child whose parent does not list it has a parent pointer that is wrong.
+fn internal_node_child_index(node: &[u8], child_page_num: usize) -> Result<usize, DbError> {
+    internal_node_children(node)
+        .iter()
+        .position(|&page_num| page_num == child_page_num)
+        .ok_or(DbError::CorruptPage(child_page_num))
+}
 
-    if !splitting_root {
-        internal_node_insert(table, get_node_parent(old_node_buffer) as usize, new_page_num);
-        new_node_buffer.copy_from_slice(table.pager.get_page(new_page_num));
-        set_node_parent(new_node_buffer, get_node_parent(old_node_buffer));
+fn leaf_node_cells(node: &[u8]) -> Vec<Vec<u8>> {
+    (0..leaf_node_num_cells(node) as usize)
+        .map(|i| leaf_node_cell(node, i).to_vec())
+        .collect()
+}
+
+/// Replaces the cells of a leaf, packing them against the end of the page.
+pub fn write_leaf_node_cells(node: &mut [u8], cells: &[Vec<u8>]) {
+    let mut cell_offset = usable_page_size(node.len());
+    for (i, cell) in cells.iter().enumerate() {
+        cell_offset -= cell.len();
+        node[cell_offset..cell_offset + cell.len()].copy_from_slice(cell);
+        set_leaf_node_cell_offset(node, i, cell_offset);
     }
+    set_leaf_node_num_cells(node, cells.len() as u32);
+    set_leaf_node_cell_content_start(node, cell_offset);
+    set_leaf_node_fragmented_bytes(node, 0);
+}
+
+/// Whether the cells fit in one leaf of the given page size.
+pub fn leaf_node_cells_fit(cells: &[Vec<u8>], page_size: usize) -> bool {
+    cells.iter().map(|cell| cell_footprint(cell)).sum::<usize>() <= leaf_node_space_for_cells(page_size)
+}
+
+// Where to split cells that do not fit in one leaf into two that do: the first
+// cell whose footprint takes the left half past the middle.
+fn leaf_node_split_index(cells: &[Vec<u8>]) -> usize {
+    let total: usize = cells.iter().map(|cell| cell_footprint(cell)).sum();
+    let mut left = 0;
+    for (i, cell) in cells.iter().enumerate() {
+        left += cell_footprint(cell);
+        if left * 2 >= total {
+            // the right half must keep at least one cell
+            return (i + 1).min(cells.len() - 1);
+        }
+    }
+    cells.len() - 1
+}
+
+/// Replaces the row under the cursor, the key stays the same. The new row may
+/// be longer or shorter than the old one, so the leaf is split when it no
+/// longer fits and rebalanced when it becomes underfull. The old row's overflow
+/// chain is freed before the new row spills to one of its own.
+pub fn leaf_node_update(cursor: &mut Cursor, row: &Row) -> Result<(), DbError> {
+    let old_cell = leaf_node_cell(cursor.table.pager.get_page_ref(cursor.page_num)?, cursor.cell_num).to_vec();
+    free_overflow_chain(&mut cursor.table.pager, &old_cell)?;
+    let cell = leaf_cell(&mut cursor.table.pager, &row.serialize_row())?;
+    let node = cursor.table.pager.get_page(cursor.page_num)?;
+    leaf_node_remove_cell(node, cursor.cell_num);
+    if leaf_node_free_space(node) < cell_footprint(&cell) {
+        return leaf_node_split_and_insert(cursor, &cell);
+    }
+    leaf_node_insert_cell(node, cursor.cell_num, &cell);
+    if node_underflows(node, cursor.page_num)? {
+        rebalance_after_delete(cursor.table, cursor.page_num)?;
+    }
+    Ok(())
+}
 
-    // copy the buffer back to the page
-    let parent = table.pager.get_page(parent_page_num);
-    parent.copy_from_slice(parent_node_buffer);
+pub fn leaf_node_delete(cursor: &mut Cursor) -> Result<(), DbError> {
+    let node = cursor.table.pager.get_page(cursor.page_num)?;
+    let num_cells = leaf_node_num_cells(node) as usize;
+    if cursor.cell_num >= num_cells {
+        eprintln!("Tried to delete cell {} >= {}.", cursor.cell_num, num_cells);
+        panic!("Tried to delete cell out of bounds.");
+    }
+    let cell = leaf_node_cell(node, cursor.cell_num).to_vec();
+    free_overflow_chain(&mut cursor.table.pager, &cell)?;
+    let node = cursor.table.pager.get_page(cursor.page_num)?;
+    leaf_node_remove_cell(node, cursor.cell_num);
+
+    rebalance_after_delete(cursor.table, cursor.page_num)
+}
+
+// Walks from a node that lost a cell or a child up to the root. On the way each
+// node either gets its key in the parent refreshed or, when it has dropped below
+// minimum occupancy, is merged with or borrows from a sibling.
+fn rebalance_after_delete(table: &mut Table, page_num: usize) -> Result<(), DbError> {
+    let mut page_num = page_num;
+    loop {
+        let node = table.pager.get_page_ref(page_num)?.to_vec();
+        if is_node_root(&node, page_num)? {
+            return collapse_root(table);
+        }
+        let parent_page_num = get_node_parent(&node) as usize;
+        let parent = table.pager.get_page_ref(parent_page_num)?.to_vec();
+        let child_num = internal_node_child_index(&parent, page_num)?;
+        if node_underflows(&node, page_num)? {
+            merge_or_redistribute(table, parent_page_num, child_num)?;
+        } else if child_num < internal_node_num_keys(&parent) as usize {
+            let max_key = get_node_max_key(table, page_num, &node)?;
+            update_internal_node_key(table.pager.get_page(parent_page_num)?, max_key, child_num);
+        }
+        page_num = parent_page_num;
+    }
+}
 
-    let old_node = table.pager.get_page(old_page_num);
-    old_node.copy_from_slice(old_node_buffer);
+fn node_underflows(node: &[u8], page_num: usize) -> Result<bool, DbError> {
+    Ok(match get_node_type(node, page_num)? {
+        NodeType::NodeLeaf => leaf_node_used_space(node) < leaf_node_min_used_space(node.len()),
+        NodeType::NodeInternal => {
+            internal_node_num_keys(node) < internal_node_min_cells(node.len()) as u32
+        }
+    })
+}
 
-    let new_node = table.pager.get_page(new_page_num);
-    new_node.copy_from_slice(new_node_buffer);
+// Fixes an underfull child by pairing it with its left sibling (or its right
+// sibling when it is the first child). If both fit in one node they are merged
+// into the left one, otherwise their contents are split evenly between them.
+fn merge_or_redistribute(table: &mut Table, parent_page_num: usize, child_num: usize) -> Result<(), DbError> {
+    let page_size = table.pager.page_size;
+    let parent = table.pager.get_page_ref(parent_page_num)?.to_vec();
+    let mut children = internal_node_children(&parent);
+    let mut keys = internal_node_keys(&parent);
+
+    let left_num = child_num.saturating_sub(1);
+    let left_page_num = children[left_num];
+    let right_page_num = children[left_num + 1];
+    let left_node = table.pager.get_page_ref(left_page_num)?.to_vec();
+    let right_node = table.pager.get_page_ref(right_page_num)?.to_vec();
+
+    let merged = match get_node_type(&left_node, left_page_num)? {
+        NodeType::NodeLeaf => {
+            let mut cells = leaf_node_cells(&left_node);
+            cells.extend(leaf_node_cells(&right_node));
+            if leaf_node_cells_fit(&cells, page_size) {
+                let next_leaf = leaf_node_next_leaf(&right_node);
+                let left = table.pager.get_page(left_page_num)?;
+                write_leaf_node_cells(left, &cells);
+                set_next_leaf(left, next_leaf);
+                if next_leaf != 0 {
+                    set_prev_leaf(table.pager.get_page(next_leaf as usize)?, left_page_num as u32);
+                }
+                true
+            } else {
+                let right_cells = cells.split_off(leaf_node_split_index(&cells));
+                write_leaf_node_cells(table.pager.get_page(left_page_num)?, &cells);
+                write_leaf_node_cells(table.pager.get_page(right_page_num)?, &right_cells);
+                false
+            }
+        }
+        NodeType::NodeInternal => {
+            // the left node's max key separates the two halves once they are joined
+            let left_max_key = get_node_max_key(table, left_page_num, &left_node)?;
+            let mut pair_children = internal_node_children(&left_node);
+            pair_children.extend(internal_node_children(&right_node));
+            let mut pair_keys = internal_node_keys(&left_node);
+            pair_keys.push(left_max_key);
+            pair_keys.extend(internal_node_keys(&right_node));
+            if pair_keys.len() <= internal_node_max_cells(page_size) {
+                write_internal_node(table.pager.get_page(left_page_num)?, &pair_children, &pair_keys);
+                for &page_num in &pair_children {
+                    set_node_parent(table.pager.get_page(page_num)?, left_page_num as u32);
+                }
+                true
+            } else {
+                let split_num = pair_children.len() / 2;
+                let right_children = pair_children.split_off(split_num);
+                let right_keys = pair_keys.split_off(split_num);
+                pair_keys.pop();
+                write_internal_node(table.pager.get_page(left_page_num)?, &pair_children, &pair_keys);
+                write_internal_node(table.pager.get_page(right_page_num)?, &right_children, &right_keys);
+                for &page_num in &pair_children {
+                    set_node_parent(table.pager.get_page(page_num)?, left_page_num as u32);
+                }
+                for &page_num in &right_children {
+                    set_node_parent(table.pager.get_page(page_num)?, right_page_num as u32);
+                }
+                false
+            }
+        }
+    };
+
+    if merged {
+        children.remove(left_num + 1);
+        keys.remove(left_num);
+        table.pager.free_page(right_page_num)?;
+    }
+    // the pair's max keys may have moved, refresh them from the new contents
+    for num in left_num..(left_num + 2).min(keys.len()) {
+        let node = table.pager.get_page_ref(children[num])?.to_vec();
+        keys[num] = get_node_max_key(table, children[num], &node)?;
+    }
+    write_internal_node(table.pager.get_page(parent_page_num)?, &children, &keys);
+    Ok(())
+}
+
+// A root left with a single child is replaced by that child, which shrinks the tree by one level.
+fn collapse_root(table: &mut Table) -> Result<(), DbError> {
+    let root = table.pager.get_page_ref(table.root_page_num)?;
+    if matches!(get_node_type(root, table.root_page_num)?, NodeType::NodeLeaf) || internal_node_num_keys(root) > 0 {
+        return Ok(());
+    }
+    let child_page_num =
+        usize::from_le_bytes(internal_node_right_child_unmut(root).try_into().unwrap());
+    let child = table.pager.get_page_ref(child_page_num)?.to_vec();
+    let child_type = get_node_type(&child, child_page_num)?;
+    let root = table.pager.get_page(table.root_page_num)?;
+    root.copy_from_slice(&child);
+    set_node_root(root, true);
+    if let NodeType::NodeInternal = child_type {
+        for page_num in internal_node_children(&child) {
+            set_node_parent(table.pager.get_page(page_num)?, table.root_page_num as u32);
+        }
+    }
+    table.pager.free_page(child_page_num)
 }
 
 pub fn internal_node_num_keys(node: &[u8]) -> u32 {
@@ -473,11 +756,6 @@ fn increment_internal_node_num_keys(node: &mut [u8]) {
     set_internal_node_num_keys(node, num_keys + 1);
 }
 
-fn decrement_internal_node_num_keys(node: &mut [u8]) {
-    let num_keys = internal_node_num_keys(node);
-    set_internal_node_num_keys(node, num_keys - 1);
-}
-
 pub fn set_internal_node_num_keys(node: &mut [u8], num_keys: u32) {
     node[INTERNAL_NODE_NUM_KEYS_OFFSET..INTERNAL_NODE_NUM_KEYS_OFFSET + INTERNAL_NODE_NUM_KEYS_SIZE]
         .as_mut().copy_from_slice(&num_keys.to_le_bytes());
@@ -509,8 +787,8 @@ fn internal_node_cell_mut(node: &mut [u8], cell_num: usize) -> &mut [u8] {
     &mut node[cell_offset..cell_offset + INTERNAL_NODE_CELL_SIZE]
 }
 
-fn internal_node_child_page_num(node: &mut [u8], cell_num: usize) -> usize {
-    usize::from_le_bytes(internal_node_cell_mut(node, cell_num)[..INTERNAL_NODE_CHILD_SIZE].try_into().unwrap())
+fn internal_node_child_page_num(node: &[u8], cell_num: usize) -> usize {
+    usize::from_le_bytes(internal_node_cell(node, cell_num)[..INTERNAL_NODE_CHILD_SIZE].try_into().unwrap())
 }
 
 pub fn internal_node_key(node: &[u8], cell_num: usize) -> &[u8] {
@@ -560,22 +838,33 @@ pub fn leaf_node_next_leaf(node: &[u8]) -> u32 {
     )
 }
 
-pub fn get_node_max_key(table: &Table, node: &[u8]) -> u32 {
-    match get_node_type(node) {
+pub fn leaf_node_prev_leaf(node: &[u8]) -> u32 {
+    u32::from_le_bytes(
+        node[LEAF_NODE_PREV_LEAF_OFFSET..LEAF_NODE_PREV_LEAF_OFFSET + LEAF_NODE_PREV_LEAF_SIZE]
+            .try_into()
+            .unwrap(),
+    )
+}
+
+/// Largest key under `node`, read from `page_num`, following right children
+/// down to a leaf.
+pub fn get_node_max_key(table: &mut Table, page_num: usize, node: &[u8]) -> Result<u32, DbError> {
+    let page_size = table.pager.page_size;
+    match get_node_type(node, page_num)? {
         NodeType::NodeInternal => {
             let right_child_page_num =
                 usize::from_le_bytes(internal_node_right_child_unmut(node).try_into().unwrap());
-            let right_child = table.pager.get_page_unmut(right_child_page_num);
-            get_node_max_key(table, right_child)
+            let right_child_buffer = &mut vec![b'\0'; page_size];
+            right_child_buffer.copy_from_slice(table.pager.get_page_ref(right_child_page_num)?);
+            get_node_max_key(table, right_child_page_num, right_child_buffer)
         }
         NodeType::NodeLeaf => {
             let num_cells = leaf_node_num_cells(node);
-            let max_key = u32::from_le_bytes(
+            Ok(u32::from_le_bytes(
                 leaf_node_key(node, num_cells as usize - 1)
                     .try_into()
                     .unwrap(),
-            );
-            max_key
+            ))
         }
     }
 }
@@ -596,49 +885,80 @@ pub fn update_internal_node_key(node: &mut [u8], new_key: u32, old_child_num: us
     let cell = internal_node_cell_mut(node, old_child_num);
     cell[INTERNAL_NODE_CHILD_SIZE..INTERNAL_NODE_CHILD_SIZE + INTERNAL_NODE_KEY_SIZE].copy_from_slice(&new_key.to_le_bytes());
 }
-pub fn print_node_contents(node: &mut [u8]) {
-    println!("-------------------------------------------------");
-    match get_node_type(node) {
+/// Renders the tree under the root one node per line, indented by depth. Leaves
+/// list their keys, internal nodes list each child followed by its key.
+pub fn format_tree(table: &mut Table) -> Result<String, DbError> {
+    let mut out = String::new();
+    format_node(table, table.root_page_num, 0, &mut out)?;
+    Ok(out)
+}
+
+fn format_node(table: &mut Table, page_num: usize, indent_level: usize, out: &mut String) -> Result<(), DbError> {
+    let node = table.pager.get_page_ref(page_num)?.to_vec();
+    let indent = "  ".repeat(indent_level);
+    match get_node_type(&node, page_num)? {
+        NodeType::NodeLeaf => {
+            let num_cells = leaf_node_num_cells(&node);
+            out.push_str(&format!("{}- leaf (page {}, size {})\n", indent, page_num, num_cells));
+            for cell_num in 0..num_cells as usize {
+                let key = u32::from_le_bytes(leaf_node_key(&node, cell_num).try_into().unwrap());
+                out.push_str(&format!("{}  - {}\n", indent, key));
+            }
+        }
         NodeType::NodeInternal => {
-            println!("Node type: Internal");
-            println!("Is root: {}", is_node_root(node));
-            println!("Num keys: {}", internal_node_num_keys(node));
-            println!("Parent: {}", get_node_parent(node));
-            println!(
-                "Right child: {}",
-                usize::from_le_bytes(internal_node_right_child(node).try_into().unwrap())
-            );
-            for i in 0..internal_node_num_keys(node) {
-                println!(
-                    "Key: {}",
-                    u32::from_le_bytes(internal_node_key(node, i as usize).try_into().unwrap())
-                );
-                println!(
-                    "Child: {}",
-                    usize::from_le_bytes(internal_node_child(node, i as usize).try_into().unwrap())
-                );
+            let keys = internal_node_keys(&node);
+            out.push_str(&format!("{}- internal (page {}, size {})\n", indent, page_num, keys.len()));
+            for (child_num, child_page_num) in internal_node_children(&node).into_iter().enumerate() {
+                format_node(table, child_page_num, indent_level + 1, out)?;
+                if let Some(key) = keys.get(child_num) {
+                    out.push_str(&format!("{}  - key {}\n", indent, key));
+                }
             }
         }
+    }    Ok(())
+}
+
+/// Renders the tree as a Graphviz DOT graph. Each node is labelled with its page
+/// number and keys, solid edges are child pointers and dotted edges link each
+/// leaf to its `next_leaf`.
+pub fn format_dot(table: &mut Table) -> Result<String, DbError> {
+    let mut out = String::from("digraph btree {\n    node [shape=box];\n");
+    format_dot_node(table, table.root_page_num, &mut out)?;
+    out.push_str("}\n");
+    Ok(out)
+}
+
+fn format_dot_node(table: &mut Table, page_num: usize, out: &mut String) -> Result<(), DbError> {
+    let node = table.pager.get_page_ref(page_num)?.to_vec();
+    match get_node_type(&node, page_num)? {
         NodeType::NodeLeaf => {
-            println!("Node type: Leaf");
-            println!("Is root: {}", is_node_root(node));
-            println!("Num cells: {}", leaf_node_num_cells(node));
-            println!("Parent: {}", get_node_parent(node));
-            for i in 0..leaf_node_num_cells(node) {
-                print_cell(node, i as usize);
+            let keys: Vec<String> = (0..leaf_node_num_cells(&node) as usize)
+                .map(|cell_num| u32::from_le_bytes(leaf_node_key(&node, cell_num).try_into().unwrap()).to_string())
+                .collect();
+            out.push_str(&format!(
+                "    page{} [label=\"page {} (leaf)\\n{}\"];\n",
+                page_num,
+                page_num,
+                keys.join(", ")
+            ));
+            let next_leaf = leaf_node_next_leaf(&node);
+            if next_leaf != 0 {
+                out.push_str(&format!("    page{} -> page{} [style=dotted];\n", page_num, next_leaf));
+            }
+        }
+        NodeType::NodeInternal => {
+            let keys: Vec<String> = internal_node_keys(&node).iter().map(u32::to_string).collect();
+            out.push_str(&format!(
+                "    page{} [label=\"page {} (internal)\\n{}\"];\n",
+                page_num,
+                page_num,
+                keys.join(", ")
+            ));
+            for child_page_num in internal_node_children(&node) {
+                out.push_str(&format!("    page{} -> page{};\n", page_num, child_page_num));
+                format_dot_node(table, child_page_num, out)?;
             }
         }
     }
-    println!("-------------------------------------------------");
-}
-
-pub fn print_cell(node: &mut [u8], cell_num: usize) {
-    println!("-------------------------------------------------");
-    println!(
-        "Key: {}",
-        u32::from_le_bytes(leaf_node_key(node, cell_num as usize).try_into().unwrap())
-    );
-    let row = Row::deserialize_row(leaf_node_value(node, cell_num as usize));
-    println!("Row: ({}, {}, {})", row.id, row.username, row.email);
-    println!("-------------------------------------------------");
+    Ok(())
 }
@@ -0,0 +1,107 @@
+use crate::constants::*;
+use crate::enums::DbError;
+use crate::pager::Pager;
+
+/// Builds the leaf cell for a serialized row. A payload too large to keep in
+/// the leaf leaves a prefix in the cell and the rest in a new overflow chain.
+pub fn leaf_cell(pager: &mut Pager, payload: &[u8]) -> Result<Vec<u8>, DbError> {
+    let page_size = pager.page_size;
+    let mut cell = Vec::with_capacity(leaf_node_cell_size(payload.len(), page_size));
+    cell.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    if payload.len() <= leaf_node_max_local_payload(page_size) {
+        cell.extend_from_slice(payload);
+        return Ok(cell);
+    }
+    let local_size = leaf_node_spilled_local_payload(page_size);
+    cell.extend_from_slice(&payload[..local_size]);
+    let first_page = write_overflow_chain(pager, &payload[local_size..])?;
+    cell.extend_from_slice(&(first_page as u32).to_le_bytes());
+    Ok(cell)
+}
+
+pub fn cell_payload_size(cell: &[u8]) -> usize {
+    u32::from_le_bytes(cell[..LEAF_NODE_PAYLOAD_SIZE_SIZE].try_into().unwrap()) as usize
+}
+
+/// Size of the cell at the start of `area`, or `None` when it runs past the end
+/// of it or is too short to hold a key.
+pub fn cell_size_within(area: &[u8], page_size: usize) -> Option<usize> {
+    let payload_size = area.get(..LEAF_NODE_PAYLOAD_SIZE_SIZE).map(cell_payload_size)?;
+    let cell_size = leaf_node_cell_size(payload_size, page_size);
+    (payload_size >= ROW_MIN_SIZE && cell_size <= area.len()).then_some(cell_size)
+}
+
+/// First page of the cell's overflow chain, or `None` when its payload is all in the leaf.
+pub fn cell_overflow_page(cell: &[u8], page_size: usize) -> Option<usize> {
+    if cell_payload_size(cell) <= leaf_node_max_local_payload(page_size) {
+        return None;
+    }
+    let offset = LEAF_NODE_PAYLOAD_OFFSET + leaf_node_spilled_local_payload(page_size);
+    Some(u32::from_le_bytes(cell[offset..offset + LEAF_NODE_OVERFLOW_PAGE_SIZE].try_into().unwrap()) as usize)
+}
+
+/// The payload of a leaf cell, read back from its overflow chain when it spilled.
+pub fn cell_payload(pager: &mut Pager, cell: &[u8]) -> Result<Vec<u8>, DbError> {
+    let page_size = pager.page_size;
+    let payload_size = cell_payload_size(cell);
+    let first_page = match cell_overflow_page(cell, page_size) {
+        Some(first_page) => first_page,
+        None => return Ok(cell[LEAF_NODE_PAYLOAD_OFFSET..LEAF_NODE_PAYLOAD_OFFSET + payload_size].to_vec()),
+    };
+    let local_size = leaf_node_spilled_local_payload(page_size);
+    let mut payload = Vec::with_capacity(payload_size);
+    payload.extend_from_slice(&cell[LEAF_NODE_PAYLOAD_OFFSET..LEAF_NODE_PAYLOAD_OFFSET + local_size]);
+    let mut page_num = first_page;
+    while payload.len() < payload_size {
+        let page = overflow_page(pager, page_num)?;
+        let chunk_size = (payload_size - payload.len()).min(overflow_page_capacity(page_size));
+        payload.extend_from_slice(&page[OVERFLOW_PAGE_HEADER_SIZE..OVERFLOW_PAGE_HEADER_SIZE + chunk_size]);
+        page_num = overflow_page_next(page);
+    }
+    Ok(payload)
+}
+
+/// Puts the pages of the cell's overflow chain on the free list. Does nothing
+/// for a cell that did not spill.
+pub fn free_overflow_chain(pager: &mut Pager, cell: &[u8]) -> Result<(), DbError> {
+    let mut page_num = cell_overflow_page(cell, pager.page_size).unwrap_or(0);
+    while page_num != 0 {
+        let next = overflow_page_next(overflow_page(pager, page_num)?);
+        pager.free_page(page_num)?;
+        page_num = next;
+    }
+    Ok(())
+}
+
+pub fn overflow_page_next(page: &[u8]) -> usize {
+    u32::from_le_bytes(
+        page[OVERFLOW_PAGE_NEXT_OFFSET..OVERFLOW_PAGE_NEXT_OFFSET + OVERFLOW_PAGE_NEXT_SIZE].try_into().unwrap(),
+    ) as usize
+}
+
+// Writes `data` to as many new pages as it needs, returns the first one.
+fn write_overflow_chain(pager: &mut Pager, data: &[u8]) -> Result<usize, DbError> {
+    let page_nums = data
+        .chunks(overflow_page_capacity(pager.page_size))
+        .map(|_| pager.allocate_page())
+        .collect::<Result<Vec<usize>, DbError>>()?;
+    for (i, chunk) in data.chunks(overflow_page_capacity(pager.page_size)).enumerate() {
+        let page = pager.get_page(page_nums[i])?;
+        page.fill(0);
+        page[NODE_TYPE_OFFSET] = OVERFLOW_PAGE_TYPE;
+        let next = page_nums.get(i + 1).copied().unwrap_or(0);
+        page[OVERFLOW_PAGE_NEXT_OFFSET..OVERFLOW_PAGE_NEXT_OFFSET + OVERFLOW_PAGE_NEXT_SIZE]
+            .copy_from_slice(&(next as u32).to_le_bytes());
+        page[OVERFLOW_PAGE_HEADER_SIZE..OVERFLOW_PAGE_HEADER_SIZE + chunk.len()].copy_from_slice(chunk);
+    }
+    Ok(page_nums[0])
+}
+
+// A chain leading to a page that is not an overflow page is reported as corrupt.
+fn overflow_page(pager: &mut Pager, page_num: usize) -> Result<&[u8], DbError> {
+    let page = pager.get_page_ref(page_num)?;
+    if page[NODE_TYPE_OFFSET] != OVERFLOW_PAGE_TYPE {
+        return Err(DbError::CorruptPage(page_num));
+    }
+    Ok(page)
+}
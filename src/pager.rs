@@ -1,118 +1,708 @@
+use std::collections::HashMap;
 use std::io::{Read, Seek, Write};
+use crate::checksum::crc32_update;
+use crate::config::DbConfig;
 use crate::constants::*;
+use crate::enums::{DbError, JournalMode, Synchronous};
+use crate::header::{free_list_head, set_free_list_head, validate_page_size, FileHeader};
+use crate::journal::{rollback_hot_journal, RollbackJournal};
+use crate::wal::Wal;
+
+struct Frame {
+    page_num: usize,
+    data: Box<[u8]>,
+    pin_count: usize,
+    dirty: bool,
+    last_used: u64,
+}
+
+// What protects the database file from a crash part way through a commit.
+enum Journal {
+    // changed pages go to the log, the file only changes on a checkpoint
+    Wal(Wal),
+    // the file is written in place once the original pages are saved
+    Rollback(RollbackJournal),
+    Off,
+}
+
+// A point an open transaction can be rolled back to. The transaction itself is
+// the unnamed savepoint at the bottom of the stack.
+struct Savepoint {
+    name: Option<String>,
+    num_pages: usize,
+    // contents of each page as the savepoint found it, taken on first access
+    // after the savepoint was set and before a newer one was
+    original_pages: HashMap<usize, Box<[u8]>>,
+}
 
 pub struct Pager {
     file: std::fs::File,
+    file_name: String,
+    journal: Journal,
+    synchronous: Synchronous,
+    // empty unless an explicit transaction is open
+    savepoints: Vec<Savepoint>,
+    pub page_size: usize,
     pub num_pages: usize,
-    pub pages: Vec<Option<[u8; PAGE_SIZE]>>,
+    pool_size: usize,
+    frames: Vec<Frame>,
+    // maps a page number to the index of the frame holding it
+    page_table: HashMap<usize, usize>,
+    // logical clock used to find the least recently used frame
+    clock: u64,
+    // dirty pages written out of the buffer pool so far
+    page_writes: usize,
 }
 
 impl Pager {
-    pub fn open(file_name: &str) -> Self {
-        let file = match std::fs::OpenOptions::new()
+    pub fn open(file_name: &str, config: &DbConfig) -> Result<Self, DbError> {
+        let pool_size = config.pool_size;
+        if pool_size < MIN_POOL_SIZE {
+            return Err(DbError::InvalidArgument(format!(
+                "buffer pool needs at least {} frames, got {}",
+                MIN_POOL_SIZE, pool_size
+            )));
+        }
+        let synchronous = config.synchronous;
+        let is_new_file = !std::path::Path::new(file_name).exists();
+        let mut file = std::fs::OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
-            .open(file_name)
-        {
-            Ok(file) => file,
-            Err(err) => {
-                eprintln!("Error opening file: {}", err);
-                panic!("Error opening file.");
+            .truncate(false)
+            .open(file_name)?;
+        if is_new_file && synchronous != Synchronous::Off {
+            sync_directory(file_name)?;
+        }
+        // undo a transaction a crash left half written, then bring the file up
+        // to date with whatever the last session committed to a log
+        rollback_hot_journal(file_name, &mut file)?;
+        let wal_file_name = wal_file_name(file_name);
+        let wal = if config.journal_mode == JournalMode::Wal || std::path::Path::new(&wal_file_name).exists() {
+            let mut wal = open_wal(&wal_file_name, synchronous)?;
+            wal.checkpoint(&mut file, synchronous)?;
+            Some(wal)
+        } else {
+            None
+        };
+        let file_length = file.metadata()?.len() as usize;
+        let page_size = if file_length == 0 {
+            validate_page_size(config.page_size)?;
+            config.page_size
+        } else {
+            // existing files keep the page size they were created with
+            let mut header_bytes = [0; HEADER_SIZE];
+            file.seek(std::io::SeekFrom::Start(0))?;
+            if file.read_exact(&mut header_bytes).is_err() {
+                return Err(DbError::InvalidFile(
+                    "file is too short to hold a header".to_string(),
+                ));
             }
+            FileHeader::read(&header_bytes)?.page_size as usize
         };
-        let mut pages = Vec::new();
-        let file_length = file.metadata().unwrap().len() as usize;
-        let num_pages = file_length / PAGE_SIZE;
-        if file_length % PAGE_SIZE != 0 {
-            eprintln!("Db file is not a whole number of pages. Corrupt file.");
-            panic!("Db file is not a whole number of pages.");
-        }
-        for _ in 0..TABLE_MAX_PAGES {
-            pages.push(None);
+        let num_pages = file_length / page_size;
+        if !file_length.is_multiple_of(page_size) {
+            return Err(DbError::InvalidFile(
+                "file is not a whole number of pages".to_string(),
+            ));
         }
-        Pager {
+        let journal = match (config.journal_mode, wal) {
+            (JournalMode::Wal, Some(mut wal)) => {
+                wal.reset(page_size, synchronous)?;
+                Journal::Wal(wal)
+            }
+            (journal_mode, wal) => {
+                // other modes leave no log behind
+                if wal.is_some() {
+                    std::fs::remove_file(&wal_file_name)?;
+                }
+                match journal_mode {
+                    JournalMode::Rollback => Journal::Rollback(RollbackJournal::new(file_name, page_size)),
+                    _ => Journal::Off,
+                }
+            }
+        };
+        Ok(Pager {
             file,
+            file_name: file_name.to_string(),
+            journal,
+            synchronous,
+            savepoints: Vec::new(),
+            page_size,
             num_pages,
-            pages,
-        }
+            pool_size,
+            frames: Vec::with_capacity(pool_size),
+            page_table: HashMap::new(),
+            clock: 0,
+            page_writes: 0,
+        })
     }
 
     fn file_length(&self) -> usize {
         self.file.metadata().unwrap().len() as usize
     }
 
-    pub fn pager_flush(&mut self, page_num: usize) {
-        let page = self.pages[page_num].as_ref().unwrap_or_else(|| {
-            eprintln!("Tried to flush null page.");
-            panic!("Tried to flush null page.");
+    pub fn journal_mode(&self) -> JournalMode {
+        match self.journal {
+            Journal::Wal(_) => JournalMode::Wal,
+            Journal::Rollback(_) => JournalMode::Rollback,
+            Journal::Off => JournalMode::Off,
+        }
+    }
+
+    /// Switches journal mode between transactions. Leaving WAL mode checkpoints
+    /// the log and deletes it.
+    pub fn set_journal_mode(&mut self, journal_mode: JournalMode) -> Result<(), DbError> {
+        if journal_mode == self.journal_mode() {
+            return Ok(());
+        }
+        if self.in_transaction() {
+            return Err(DbError::TransactionOpen);
+        }
+        self.commit();
+        if let Journal::Wal(wal) = &mut self.journal {
+            wal.checkpoint(&mut self.file, self.synchronous)?;
+            self.journal = Journal::Off;
+            std::fs::remove_file(wal_file_name(&self.file_name))?;
+        }
+        self.journal = match journal_mode {
+            JournalMode::Wal => {
+                let mut wal = open_wal(&wal_file_name(&self.file_name), self.synchronous)?;
+                wal.reset(self.page_size, self.synchronous)?;
+                Journal::Wal(wal)
+            }
+            JournalMode::Rollback => Journal::Rollback(RollbackJournal::new(&self.file_name, self.page_size)),
+            JournalMode::Off => Journal::Off,
+        };
+        Ok(())
+    }
+
+    /// Makes every change since the last commit durable. In WAL mode the changed
+    /// pages are appended to the log followed by a synced commit record, in
+    /// journal mode they are written to the file once their originals are saved
+    /// and the journal is deleted after the file is synced. With no journal the
+    /// file is written in place and synced unless synchronous is off.
+    pub fn commit(&mut self) {
+        self.savepoints.clear();
+        let mut dirty_frames: Vec<usize> = (0..self.frames.len())
+            .filter(|&frame_index| self.frames[frame_index].dirty)
+            .collect();
+        dirty_frames.sort_unstable_by_key(|&frame_index| self.frames[frame_index].page_num);
+        if let Journal::Rollback(journal) = &mut self.journal {
+            // save every original first so the journal is synced only once
+            for &frame_index in &dirty_frames {
+                journal.save_page(self.frames[frame_index].page_num, &mut self.file).unwrap_or_else(|e| {
+                    eprintln!("Error writing rollback journal: {}", e);
+                    panic!("Error writing rollback journal.");
+                });
+            }
+        }
+        for frame_index in dirty_frames {
+            self.write_frame(frame_index);
+        }
+        match &mut self.journal {
+            Journal::Wal(wal) => {
+                if !wal.has_uncommitted_frames() {
+                    return;
+                }
+                wal.commit(self.num_pages, self.synchronous);
+                if wal.num_frames() >= WAL_AUTOCHECKPOINT_FRAMES {
+                    self.checkpoint();
+                }
+            }
+            Journal::Rollback(journal) => {
+                // the file must be on disk before the journal that can undo it goes away
+                let synced = match self.synchronous {
+                    Synchronous::Off => Ok(()),
+                    Synchronous::Normal | Synchronous::Full => self.file.sync_all(),
+                };
+                synced.and_then(|_| journal.finish()).unwrap_or_else(|e| {
+                    eprintln!("Error committing: {}", e);
+                    panic!("Error committing.");
+                });
+            }
+            Journal::Off => {
+                // nothing can undo a torn commit, but what was written is synced all the same
+                let synced = match self.synchronous {
+                    Synchronous::Off => Ok(()),
+                    Synchronous::Normal | Synchronous::Full => self.file.sync_all(),
+                };
+                synced.unwrap_or_else(|e| {
+                    eprintln!("Error committing: {}", e);
+                    panic!("Error committing.");
+                });
+            }
+        }
+    }
+
+    pub fn synchronous(&self) -> Synchronous {
+        self.synchronous
+    }
+
+    pub fn set_synchronous(&mut self, synchronous: Synchronous) {
+        self.synchronous = synchronous;
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        !self.savepoints.is_empty()
+    }
+
+    /// Starts a transaction that lasts until the next `commit` or `rollback`.
+    pub fn begin(&mut self) {
+        if self.in_transaction() {
+            panic!("Tried to begin a transaction inside a transaction.");
+        }
+        self.push_savepoint(None);
+    }
+
+    /// Sets a savepoint inside the open transaction. A name may be reused, the
+    /// newest savepoint with a name is the one `release` and `rollback_to` find.
+    pub fn savepoint(&mut self, name: &str) {
+        if !self.in_transaction() {
+            panic!("Tried to set a savepoint without a transaction.");
+        }
+        self.push_savepoint(Some(name.to_string()));
+    }
+
+    fn push_savepoint(&mut self, name: Option<String>) {
+        self.savepoints.push(Savepoint {
+            name,
+            num_pages: self.num_pages,
+            original_pages: HashMap::new(),
         });
-        self.file
-            .seek(std::io::SeekFrom::Start((page_num * PAGE_SIZE) as u64))
-            .unwrap_or_else(|e| {
-                eprintln!("Error seeking: {}", e);
-                panic!("Error seeking.");
-            });
-        self.file
-            .write_all(&page[0..PAGE_SIZE])
-            .unwrap_or_else(|e| {
-                eprintln!("Error writing file: {}", e);
-                panic!("Error writing file.");
-            });
     }
 
-    pub fn get_page(&mut self, page_num: usize) -> &mut [u8] {
-        if page_num > TABLE_MAX_PAGES {
-            eprintln!(
-                "Tried to fetch page number out of bounds. {} > {}",
-                page_num, TABLE_MAX_PAGES
-            );
-            panic!("Tried to fetch page number out of bounds.");
-        }
-        if self.pages[page_num].is_none() {
-            let mut page = [0; PAGE_SIZE];
-            let file_length = self.file_length();
-            let mut num_pages = file_length / PAGE_SIZE;
-            // if the file length is not a multiple of the page size, we have a partial page
-            if file_length % PAGE_SIZE > 0 {
-                num_pages += 1;
+    fn find_savepoint(&self, name: &str) -> Option<usize> {
+        self.savepoints
+            .iter()
+            .rposition(|savepoint| savepoint.name.as_deref() == Some(name))
+    }
+
+    /// Sets an unnamed savepoint for a single statement inside the open
+    /// transaction. It is ended by `end_statement`.
+    pub fn begin_statement(&mut self) {
+        if !self.in_transaction() {
+            panic!("Tried to begin a statement without a transaction.");
+        }
+        self.push_savepoint(None);
+    }
+
+    /// Ends the savepoint set by `begin_statement`. The changes of a statement
+    /// that failed part way are undone first, the rest of the transaction stays.
+    pub fn end_statement(&mut self, succeeded: bool) {
+        let index = self.savepoints.len() - 1;
+        if !succeeded {
+            let (num_pages, original_pages) = self.undo_savepoints(index);
+            self.restore_pages(num_pages, original_pages);
+        }
+        self.release_from(index);
+    }
+
+    /// Forgets the named savepoint and every newer one, keeping their changes.
+    /// Returns false when there is no such savepoint.
+    pub fn release(&mut self, name: &str) -> bool {
+        let index = match self.find_savepoint(name) {
+            Some(index) => index,
+            None => return false,
+        };
+        self.release_from(index);
+        true
+    }
+
+    fn release_from(&mut self, index: usize) {
+        // a page first touched after a released savepoint was untouched since the
+        // one below it was set, so its image holds for that savepoint too
+        for savepoint in self.savepoints.split_off(index) {
+            let below = self.savepoints.last_mut().unwrap();
+            for (page_num, original_page) in savepoint.original_pages {
+                if page_num < below.num_pages {
+                    below.original_pages.entry(page_num).or_insert(original_page);
+                }
+            }
+        }
+    }
+
+    /// Puts every page back the way the named savepoint found it. The savepoint
+    /// stays set, newer ones are forgotten. Returns false when there is no such
+    /// savepoint.
+    pub fn rollback_to(&mut self, name: &str) -> bool {
+        let index = match self.find_savepoint(name) {
+            Some(index) => index,
+            None => return false,
+        };
+        let (num_pages, original_pages) = self.undo_savepoints(index);
+        self.restore_pages(num_pages, original_pages);
+        true
+    }
+
+    /// Puts every page back the way the open transaction found it.
+    pub fn rollback(&mut self) {
+        if !self.in_transaction() {
+            panic!("Tried to roll back without a transaction.");
+        }
+        let (num_pages, original_pages) = self.undo_savepoints(0);
+        self.savepoints.clear();
+        // frames pushed out to the log since the last commit are dropped before
+        // the restored pages can be pushed out again
+        if let Journal::Wal(wal) = &mut self.journal {
+            wal.rollback();
+        }
+        self.restore_pages(num_pages, original_pages);
+    }
+
+    // Takes the page images needed to get back to the savepoint at `index` and
+    // forgets the savepoints above it. For each page the oldest image wins.
+    fn undo_savepoints(&mut self, index: usize) -> (usize, HashMap<usize, Box<[u8]>>) {
+        let mut original_pages = HashMap::new();
+        for savepoint in self.savepoints.drain(index + 1..).rev() {
+            original_pages.extend(savepoint.original_pages);
+        }
+        let savepoint = &mut self.savepoints[index];
+        original_pages.extend(std::mem::take(&mut savepoint.original_pages));
+        (savepoint.num_pages, original_pages)
+    }
+
+    fn restore_pages(&mut self, num_pages: usize, original_pages: HashMap<usize, Box<[u8]>>) {
+        // pages added since are forgotten
+        for frame_index in 0..self.frames.len() {
+            let frame = &mut self.frames[frame_index];
+            if frame.page_num != INVALID_PAGE_NUMBER && frame.page_num >= num_pages {
+                self.page_table.remove(&frame.page_num);
+                frame.page_num = INVALID_PAGE_NUMBER;
+                frame.dirty = false;
+                frame.last_used = 0;
+            }
+        }
+        self.num_pages = num_pages;
+        // evicted pages may have grown the file already, a log only grows on commit
+        if !matches!(self.journal, Journal::Wal(_)) {
+            let file_length = (self.num_pages * self.page_size) as u64;
+            if self.file.metadata().map(|metadata| metadata.len() > file_length).unwrap_or(false) {
+                self.file.set_len(file_length).unwrap_or_else(|e| {
+                    eprintln!("Error truncating file: {}", e);
+                    panic!("Error truncating file.");
+                });
             }
-            if page_num <= num_pages {
-                self.file
-                    .seek(std::io::SeekFrom::Start((page_num * PAGE_SIZE) as u64))
+        }
+        // restored pages stay dirty, evicted ones may have been written already
+        // and the next commit writes the originals back over them
+        for (page_num, original_page) in original_pages {
+            // images taken by newer savepoints can be of pages forgotten above
+            if page_num >= num_pages {
+                continue;
+            }
+            let frame_index = match self.page_table.get(&page_num) {
+                Some(&frame_index) => frame_index,
+                None => {
+                    // the image replaces the page, so it is not read back first
+                    let frame_index = self.allocate_frame();
+                    self.assign_frame(frame_index, page_num);
+                    frame_index
+                }
+            };
+            let frame = &mut self.frames[frame_index];
+            frame.data.copy_from_slice(&original_page);
+            frame.dirty = true;
+        }
+    }
+
+    /// Copies the committed pages in the write-ahead log into the database file.
+    /// Other journal modes have nothing to copy.
+    pub fn checkpoint(&mut self) {
+        if let Journal::Wal(wal) = &mut self.journal {
+            wal.checkpoint(&mut self.file, self.synchronous).unwrap_or_else(|e| {
+                eprintln!("Error checkpointing write-ahead log: {}", e);
+                panic!("Error checkpointing write-ahead log.");
+            });
+        }
+    }
+
+    // A dirty page can be written before its transaction commits when it is
+    // evicted. The log ignores such frames unless a commit record follows, and
+    // the rollback journal holds the original page before the file is touched.
+    fn write_frame(&mut self, frame_index: usize) {
+        let frame = &mut self.frames[frame_index];
+        set_page_checksum(frame.page_num, &mut frame.data);
+        match &mut self.journal {
+            Journal::Wal(wal) => wal.append_page(frame.page_num, &frame.data),
+            Journal::Rollback(journal) => {
+                journal
+                    .save_page(frame.page_num, &mut self.file)
+                    .and_then(|_| journal.sync(self.synchronous))
                     .unwrap_or_else(|e| {
-                        eprintln!("Error seeking: {}", e);
-                        panic!("Error seeking.");
+                        eprintln!("Error writing rollback journal: {}", e);
+                        panic!("Error writing rollback journal.");
                     });
-                self.file.read(&mut page).unwrap_or_else(|e| {
-                    eprintln!("Error reading file: {}", e);
-                    panic!("Error reading file.");
-                });
+                write_page(&mut self.file, frame.page_num, &frame.data);
             }
-            self.pages[page_num] = Some(page);
-            if page_num >= self.num_pages {
-                self.num_pages = page_num + 1;
+            Journal::Off => write_page(&mut self.file, frame.page_num, &frame.data),
+        }
+        frame.dirty = false;
+        self.page_writes += 1;
+    }
+
+    /// Hands out a page for reading, the frame stays clean. A page that fails
+    /// its checksum is returned as `DbError::CorruptPage`.
+    pub fn get_page_ref(&mut self, page_num: usize) -> Result<&[u8], DbError> {
+        let frame_index = self.fetch_frame(page_num)?;
+        Ok(&self.frames[frame_index].data)
+    }
+
+    /// Hands out a page for writing. The frame is marked dirty, so only pages
+    /// taken this way are written on eviction and commit. A page that fails its
+    /// checksum is returned as `DbError::CorruptPage` and is left untouched.
+    pub fn get_page(&mut self, page_num: usize) -> Result<&mut [u8], DbError> {
+        let frame_index = self.fetch_frame(page_num)?;
+        if let Some(savepoint) = self.savepoints.last_mut() {
+            if page_num < savepoint.num_pages && !savepoint.original_pages.contains_key(&page_num) {
+                savepoint.original_pages.insert(page_num, self.frames[frame_index].data.clone());
             }
         }
-        self.pages[page_num].as_mut().unwrap()
+        let frame = &mut self.frames[frame_index];
+        frame.dirty = true;
+        Ok(&mut frame.data)
+    }
+
+    /// Keeps the page in the buffer pool until a matching `unpin_page`. A page
+    /// that fails its checksum is not pinned.
+    pub fn pin_page(&mut self, page_num: usize) -> Result<(), DbError> {
+        let frame_index = self.fetch_frame(page_num)?;
+        self.frames[frame_index].pin_count += 1;
+        Ok(())
+    }
+
+    pub fn unpin_page(&mut self, page_num: usize) {
+        let frame_index = *self.page_table.get(&page_num).unwrap_or_else(|| {
+            eprintln!("Tried to unpin page {} which is not cached.", page_num);
+            panic!("Tried to unpin page which is not cached.");
+        });
+        let frame = &mut self.frames[frame_index];
+        if frame.pin_count == 0 {
+            eprintln!("Tried to unpin page {} which is not pinned.", page_num);
+            panic!("Tried to unpin page which is not pinned.");
+        }
+        frame.pin_count -= 1;
+    }
+
+    /// Number of dirty pages written to the log or the file, on eviction or commit.
+    #[cfg(test)]
+    pub fn page_writes(&self) -> usize {
+        self.page_writes
     }
 
+    /// Page numbers currently held in the buffer pool.
+    #[cfg(test)]
+    pub fn cached_pages(&self) -> Vec<usize> {
+        let mut page_nums: Vec<usize> = self.page_table.keys().copied().collect();
+        page_nums.sort_unstable();
+        page_nums
+    }
 
-    pub fn get_page_unmut(&self, page_num: usize) -> &[u8] {
-        if page_num > TABLE_MAX_PAGES {
-            eprintln!(
-                "Tried to fetch page number out of bounds. {} > {}",
-                page_num, TABLE_MAX_PAGES
-            );
-            panic!("Tried to fetch page number out of bounds.");
+    // Pages read from the log or the file are checked against their checksum,
+    // pages past the end of the file start out zeroed.
+    fn fetch_frame(&mut self, page_num: usize) -> Result<usize, DbError> {
+        self.clock += 1;
+        if let Some(&frame_index) = self.page_table.get(&page_num) {
+            self.frames[frame_index].last_used = self.clock;
+            return Ok(frame_index);
         }
-        if self.pages[page_num].is_none() {
-            panic!("Tried to fetch page number out of bounds.");
+
+        let frame_index = self.allocate_frame();
+        let num_pages_on_disk = self.file_length() / self.page_size;
+        let frame = &mut self.frames[frame_index];
+        let in_wal = match &mut self.journal {
+            Journal::Wal(wal) => wal.read_page(page_num, &mut frame.data),
+            _ => false,
+        };
+        let read = if in_wal {
+            // the log holds a newer copy than the file
+            true
+        } else if page_num < num_pages_on_disk {
+            self.file
+                .seek(std::io::SeekFrom::Start((page_num * self.page_size) as u64))
+                .unwrap_or_else(|e| {
+                    eprintln!("Error seeking: {}", e);
+                    panic!("Error seeking.");
+                });
+            self.file.read_exact(&mut frame.data).unwrap_or_else(|e| {
+                eprintln!("Error reading file: {}", e);
+                panic!("Error reading file.");
+            });
+            true
+        } else {
+            frame.data.fill(0);
+            false
+        };
+        if read && !page_checksum_matches(page_num, &frame.data) {
+            // the frame is left empty, the page stays uncached
+            frame.page_num = INVALID_PAGE_NUMBER;
+            frame.last_used = 0;
+            return Err(DbError::CorruptPage(page_num));
         }
-        self.pages[page_num].as_ref().unwrap()
+        self.assign_frame(frame_index, page_num);
+        Ok(frame_index)
     }
 
-    pub fn get_unused_page_num(&self) -> usize {
-        self.num_pages
+    // Caches `page_num` in the frame, a page past the last one is counted.
+    fn assign_frame(&mut self, frame_index: usize, page_num: usize) {
+        let frame = &mut self.frames[frame_index];
+        frame.page_num = page_num;
+        frame.pin_count = 0;
+        frame.dirty = false;
+        frame.last_used = self.clock;
+        self.page_table.insert(page_num, frame_index);
+        if page_num >= self.num_pages {
+            self.num_pages = page_num + 1;
+        }
     }
+
+    // Returns the index of an empty frame, evicting the least recently used
+    // unpinned page once the pool is full.
+    fn allocate_frame(&mut self) -> usize {
+        if self.frames.len() < self.pool_size {
+            self.frames.push(Frame {
+                page_num: INVALID_PAGE_NUMBER,
+                data: vec![0; self.page_size].into_boxed_slice(),
+                pin_count: 0,
+                dirty: false,
+                last_used: 0,
+            });
+            return self.frames.len() - 1;
+        }
+        let victim = self
+            .frames
+            .iter()
+            .enumerate()
+            .filter(|(_, frame)| frame.pin_count == 0)
+            .min_by_key(|(_, frame)| frame.last_used)
+            .map(|(frame_index, _)| frame_index)
+            .unwrap_or_else(|| {
+                eprintln!("All {} buffer pool frames are pinned.", self.pool_size);
+                panic!("All buffer pool frames are pinned.");
+            });
+        if self.frames[victim].dirty {
+            self.write_frame(victim);
+        }
+        self.page_table.remove(&self.frames[victim].page_num);
+        victim
+    }
+
+    /// Takes a page off the free list, or the page past the end of the file when the list is empty.
+    pub fn get_unused_page_num(&mut self) -> Result<usize, DbError> {
+        // the header is only dirtied when the list actually loses its head
+        let head = free_list_head(self.get_page_ref(HEADER_PAGE_NUM)?);
+        if head == 0 {
+            return Ok(self.num_pages);
+        }
+        let next = free_page_next(head, self.get_page_ref(head)?)?;
+        set_free_list_head(self.get_page(HEADER_PAGE_NUM)?, next);
+        Ok(head)
+    }
+
+    /// Takes an unused page and counts it as in use, so the next call hands out
+    /// another one even before the caller writes to it.
+    pub fn allocate_page(&mut self) -> Result<usize, DbError> {
+        let page_num = self.get_unused_page_num()?;
+        // touching the page makes the pager count it
+        self.get_page(page_num)?;
+        Ok(page_num)
+    }
+
+    /// Puts a page the tree no longer uses at the head of the free list.
+    pub fn free_page(&mut self, page_num: usize) -> Result<(), DbError> {
+        if page_num == HEADER_PAGE_NUM || page_num >= self.num_pages {
+            eprintln!("Tried to free page {} which is not a tree page.", page_num);
+            panic!("Tried to free a page which is not a tree page.");
+        }
+        let head = free_list_head(self.get_page_ref(HEADER_PAGE_NUM)?);
+        let page = self.get_page(page_num)?;
+        page.fill(0);
+        page[NODE_TYPE_OFFSET] = FREE_PAGE_TYPE;
+        page[FREE_PAGE_NEXT_OFFSET..FREE_PAGE_NEXT_OFFSET + FREE_PAGE_NEXT_SIZE]
+            .copy_from_slice(&(head as u32).to_le_bytes());
+        set_free_list_head(self.get_page(HEADER_PAGE_NUM)?, page_num);
+        Ok(())
+    }
+
+    /// Page numbers on the free list, in the order they will be reused.
+    pub fn free_list(&mut self) -> Result<Vec<usize>, DbError> {
+        let mut page_nums = Vec::new();
+        let mut page_num = free_list_head(self.get_page_ref(HEADER_PAGE_NUM)?);
+        while page_num != 0 {
+            page_nums.push(page_num);
+            page_num = free_page_next(page_num, self.get_page_ref(page_num)?)?;
+        }
+        Ok(page_nums)
+    }
+}
+
+// A page on the free list that is not a free page is reported as corrupt.
+fn free_page_next(page_num: usize, page: &[u8]) -> Result<usize, DbError> {
+    if page[NODE_TYPE_OFFSET] != FREE_PAGE_TYPE {
+        return Err(DbError::CorruptPage(page_num));
+    }
+    Ok(u32::from_le_bytes(
+        page[FREE_PAGE_NEXT_OFFSET..FREE_PAGE_NEXT_OFFSET + FREE_PAGE_NEXT_SIZE].try_into().unwrap(),
+    ) as usize)
+}
+
+// A log created at `Synchronous::Full` gets its directory synced, a commit
+// recorded in it must not vanish with the directory entry.
+fn open_wal(wal_file_name: &str, synchronous: Synchronous) -> Result<Wal, DbError> {
+    let is_new_file = !std::path::Path::new(wal_file_name).exists();
+    let wal = Wal::open(wal_file_name)?;
+    if is_new_file && synchronous == Synchronous::Full {
+        sync_directory(wal_file_name)?;
+    }
+    Ok(wal)
+}
+
+/// Syncs the directory holding `file_name` so a newly created file survives a power loss.
+#[cfg(unix)]
+pub fn sync_directory(file_name: &str) -> std::io::Result<()> {
+    let path = std::path::Path::new(file_name);
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => std::path::Path::new("."),
+    };
+    std::fs::File::open(directory)?.sync_all()
+}
+
+// Directories cannot be opened as files here, creating a file is durable once its contents are.
+#[cfg(not(unix))]
+pub fn sync_directory(_file_name: &str) -> std::io::Result<()> {
+    Ok(())
+}
+
+// The page number is checksummed too, so a page written to the wrong place is caught.
+fn page_checksum(page_num: usize, page: &[u8]) -> u32 {
+    let crc = crc32_update(0, &(page_num as u32).to_le_bytes());
+    crc32_update(crc, &page[..usable_page_size(page.len())])
+}
+
+fn set_page_checksum(page_num: usize, page: &mut [u8]) {
+    let checksum = page_checksum(page_num, page);
+    let offset = usable_page_size(page.len());
+    page[offset..].copy_from_slice(&checksum.to_le_bytes());
+}
+
+pub fn page_checksum_matches(page_num: usize, page: &[u8]) -> bool {
+    let offset = usable_page_size(page.len());
+    page[offset..] == page_checksum(page_num, page).to_le_bytes()
+}
+
+fn wal_file_name(db_file_name: &str) -> String {
+    format!("{}-wal", db_file_name)
+}
+
+fn write_page(file: &mut std::fs::File, page_num: usize, page: &[u8]) {
+    file.seek(std::io::SeekFrom::Start((page_num * page.len()) as u64))
+        .unwrap_or_else(|e| {
+            eprintln!("Error seeking: {}", e);
+            panic!("Error seeking.");
+        });
+    file.write_all(page).unwrap_or_else(|e| {
+        eprintln!("Error writing file: {}", e);
+        panic!("Error writing file.");
+    });
 }
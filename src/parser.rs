@@ -1,6 +1,7 @@
 use crate::enums::PrepareResult;
 use crate::row::Row;
-use crate::statement::{Statement, StatementType};
+use std::ops::Bound;
+use crate::statement::{KeyRange, RowUpdate, ScanOrder, Statement, StatementType};
 
 pub fn prepare_statement(input: &str) -> PrepareResult {
     if input.starts_with("insert") {
@@ -22,26 +23,149 @@ pub fn prepare_statement(input: &str) -> PrepareResult {
                 if id < 0 {
                     return PrepareResult::PrepareNegativeId;
                 }
-                return PrepareResult::PrepareSuccess(Statement {
+                PrepareResult::PrepareSuccess(Statement {
                     statement_type: StatementType::StatementInsert,
                     row_to_insert: Some(Row {
                         id,
                         username: username.to_string(),
                         email: email.to_string(),
                     }),
-                });
+                })
             }
             _ => {
                 eprintln!("Error parsing input.");
-                return PrepareResult::PrepareSyntaxError;
+                PrepareResult::PrepareSyntaxError
             }
-        };
+        }
+    } else if input.starts_with("delete") {
+        let parts: Vec<&str> = input.split_whitespace().skip(1).collect();
+        match parts.as_slice() {
+            [id_str] => match id_str.parse::<i32>() {
+                Ok(id) if id < 0 => PrepareResult::PrepareNegativeId,
+                Ok(id) => PrepareResult::PrepareSuccess(Statement {
+                    statement_type: StatementType::StatementDelete(id as u32),
+                    row_to_insert: None,
+                }),
+                Err(_) => PrepareResult::PrepareSyntaxError,
+            },
+            _ => PrepareResult::PrepareSyntaxError,
+        }
+    } else if input.starts_with("update") {
+        prepare_update(input)
     } else if input.starts_with("select") {
+        prepare_select(input)
+    } else if let Some(statement_type) = transaction_statement_type(input) {
         PrepareResult::PrepareSuccess(Statement {
-            statement_type: StatementType::StatementSelect,
+            statement_type,
             row_to_insert: None,
         })
     } else {
         PrepareResult::PrepareUnrecognizedStatement
     }
-}
\ No newline at end of file
+}
+
+fn transaction_statement_type(input: &str) -> Option<StatementType> {
+    let parts: Vec<&str> = input.split_whitespace().collect();
+    match parts.as_slice() {
+        ["begin"] => Some(StatementType::StatementBegin),
+        ["commit"] => Some(StatementType::StatementCommit),
+        ["rollback"] => Some(StatementType::StatementRollback),
+        ["savepoint", name] => Some(StatementType::StatementSavepoint(name.to_string())),
+        ["release", name] => Some(StatementType::StatementRelease(name.to_string())),
+        ["rollback", "to", name] => Some(StatementType::StatementRollbackTo(name.to_string())),
+        _ => None,
+    }
+}
+
+// update <id> set username=<username> email=<email>, either assignment may be left out
+fn prepare_update(input: &str) -> PrepareResult {
+    let parts: Vec<&str> = input.split_whitespace().skip(1).collect();
+    let (id_str, assignments) = match parts.as_slice() {
+        [id_str, "set", assignments @ ..] if !assignments.is_empty() => (id_str, assignments),
+        _ => return PrepareResult::PrepareSyntaxError,
+    };
+    let id = match id_str.parse::<i32>() {
+        Ok(id) if id < 0 => return PrepareResult::PrepareNegativeId,
+        Ok(id) => id as u32,
+        Err(_) => return PrepareResult::PrepareSyntaxError,
+    };
+    let mut update = RowUpdate {
+        username: None,
+        email: None,
+    };
+    for assignment in assignments {
+        let (column, value) = match assignment.split_once('=') {
+            Some(("username", value)) => (&mut update.username, value),
+            Some(("email", value)) => (&mut update.email, value),
+            _ => return PrepareResult::PrepareSyntaxError,
+        };
+        // the same column assigned twice
+        if column.is_some() {
+            return PrepareResult::PrepareSyntaxError;
+        }
+        *column = Some(value.to_string());
+    }
+    PrepareResult::PrepareSuccess(Statement {
+        statement_type: StatementType::StatementUpdate(id, update),
+        row_to_insert: None,
+    })
+}
+
+// select [where id <op> <id> | where id between <id> and <id>] [order by id asc|desc],
+// op is one of = < <= > >=
+fn prepare_select(input: &str) -> PrepareResult {
+    let parts: Vec<&str> = input.split_whitespace().skip(1).collect();
+    let (parts, order) = match parts.as_slice() {
+        [parts @ .., "order", "by", "id", "asc"] => (parts, ScanOrder::Ascending),
+        [parts @ .., "order", "by", "id", "desc"] => (parts, ScanOrder::Descending),
+        parts => (parts, ScanOrder::Ascending),
+    };
+    let range = match parts {
+        [] => Ok(KeyRange::all()),
+        ["where", "id", "between", start, "and", end] => parse_id(start).and_then(|start| {
+            Ok(KeyRange {
+                start: Bound::Included(start),
+                end: Bound::Included(parse_id(end)?),
+            })
+        }),
+        ["where", "id", op, id] => parse_id(id).and_then(|id| match *op {
+            "=" => Ok(KeyRange {
+                start: Bound::Included(id),
+                end: Bound::Included(id),
+            }),
+            "<" => Ok(KeyRange {
+                start: Bound::Unbounded,
+                end: Bound::Excluded(id),
+            }),
+            "<=" => Ok(KeyRange {
+                start: Bound::Unbounded,
+                end: Bound::Included(id),
+            }),
+            ">" => Ok(KeyRange {
+                start: Bound::Excluded(id),
+                end: Bound::Unbounded,
+            }),
+            ">=" => Ok(KeyRange {
+                start: Bound::Included(id),
+                end: Bound::Unbounded,
+            }),
+            _ => Err(PrepareResult::PrepareSyntaxError),
+        }),
+        _ => Err(PrepareResult::PrepareSyntaxError),
+    };
+    match range {
+        Ok(range) => PrepareResult::PrepareSuccess(Statement {
+            statement_type: StatementType::StatementSelect(range, order),
+            row_to_insert: None,
+        }),
+        Err(prepare_result) => prepare_result,
+    }
+}
+
+fn parse_id(id_str: &str) -> Result<u32, PrepareResult> {
+    match id_str.parse::<i32>() {
+        Ok(id) if id < 0 => Err(PrepareResult::PrepareNegativeId),
+        Ok(id) => Ok(id as u32),
+        Err(_) => Err(PrepareResult::PrepareSyntaxError),
+    }
+}
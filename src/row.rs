@@ -8,38 +8,53 @@ pub struct Row {
 }
 
 impl Row {
-    pub fn serialize_row(&self, destination_cell: &mut [u8]) {
-        let id_bytes = self.id.to_le_bytes();
-        let username_bytes = self.username.as_bytes();
-        let email_bytes = self.email.as_bytes();
-
-        let destination_key_slice = &mut destination_cell[..LEAF_NODE_KEY_SIZE];
-        destination_key_slice[..ID_SIZE].copy_from_slice(&id_bytes);
-
-        let cell_value_offset = LEAF_NODE_KEY_SIZE;
-        let destination_value_slice = &mut destination_cell[cell_value_offset..];
-        destination_value_slice[ID_OFFSET..ID_OFFSET + id_bytes.len()].copy_from_slice(&id_bytes);
-        destination_value_slice[USERNAME_OFFSET..USERNAME_OFFSET + username_bytes.len()]
-            .copy_from_slice(username_bytes);
-        destination_value_slice[EMAIL_OFFSET..EMAIL_OFFSET + email_bytes.len()].copy_from_slice(email_bytes);
+    pub fn serialize_row(&self) -> Vec<u8> {
+        let mut destination = Vec::with_capacity(self.serialized_size());
+        destination.extend_from_slice(&self.id.to_le_bytes());
+        for field in [&self.username, &self.email] {
+            destination.extend_from_slice(&(field.len() as u16).to_le_bytes());
+            destination.extend_from_slice(field.as_bytes());
+        }
+        destination
     }
 
     pub fn deserialize_row(source: &[u8]) -> Self {
-        let id = i32::from_le_bytes(source[ID_OFFSET..ID_OFFSET + ID_SIZE].try_into().unwrap());
-        let username =
-            std::str::from_utf8(&source[USERNAME_OFFSET..USERNAME_OFFSET + USERNAME_SIZE])
-                .unwrap()
-                .to_string();
-        let email = std::str::from_utf8(&source[EMAIL_OFFSET..EMAIL_OFFSET + EMAIL_SIZE])
-            .unwrap()
-            .to_string();
-
-        Row {
+        Self::try_deserialize_row(source).unwrap_or_else(|| {
+            eprintln!("Row of {} bytes has a field that runs past it or is not UTF-8.", source.len());
+            panic!("Error decoding row.");
+        })
+    }
+
+    /// Like `deserialize_row`, but a field running past `source` or holding
+    /// invalid UTF-8 gives `None` instead of a panic.
+    pub fn try_deserialize_row(source: &[u8]) -> Option<Self> {
+        let id = i32::from_le_bytes(source.get(ID_OFFSET..ID_OFFSET + ID_SIZE)?.try_into().unwrap());
+        let mut offset = ID_OFFSET + ID_SIZE;
+        let mut read_field = || {
+            let length = read_field_length(source, offset)?;
+            let start = offset + FIELD_LENGTH_SIZE;
+            let field = std::str::from_utf8(source.get(start..start + length)?).ok()?.to_string();
+            offset = start + length;
+            Some(field)
+        };
+        let username = read_field()?;
+        let email = read_field()?;
+
+        Some(Row {
             id,
             username,
             email,
-        }
+        })
     }
+
+    pub fn serialized_size(&self) -> usize {
+        ROW_MIN_SIZE + self.username.len() + self.email.len()
+    }
+}
+
+fn read_field_length(source: &[u8], offset: usize) -> Option<usize> {
+    let bytes = source.get(offset..offset + FIELD_LENGTH_SIZE)?;
+    Some(u16::from_le_bytes(bytes.try_into().unwrap()) as usize)
 }
 
 impl PartialEq for Row {
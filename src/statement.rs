@@ -1,3 +1,4 @@
+use std::ops::Bound;
 use crate::row::Row;
 
 pub struct Statement {
@@ -5,7 +6,62 @@ pub struct Statement {
     pub row_to_insert: Option<Row>,
 }
 
+#[allow(clippy::enum_variant_names)]
 pub enum StatementType {
     StatementInsert,
-    StatementSelect,
-}
\ No newline at end of file
+    StatementSelect(KeyRange, ScanOrder),
+    StatementDelete(u32),
+    StatementUpdate(u32, RowUpdate),
+    StatementBegin,
+    StatementCommit,
+    StatementRollback,
+    StatementSavepoint(String),
+    StatementRelease(String),
+    StatementRollbackTo(String),
+}
+
+/// Columns assigned by an `update`; `None` leaves the stored value as is.
+pub struct RowUpdate {
+    pub username: Option<String>,
+    pub email: Option<String>,
+}
+
+/// Keys a `select` returns, `select where id between 2 and 5` includes both ends.
+pub struct KeyRange {
+    pub start: Bound<u32>,
+    pub end: Bound<u32>,
+}
+
+impl KeyRange {
+    pub fn all() -> Self {
+        KeyRange {
+            start: Bound::Unbounded,
+            end: Bound::Unbounded,
+        }
+    }
+
+    /// Whether `key` comes after the end of the range, a scan in key order can stop there.
+    pub fn ends_before(&self, key: u32) -> bool {
+        match self.end {
+            Bound::Included(end) => key > end,
+            Bound::Excluded(end) => key >= end,
+            Bound::Unbounded => false,
+        }
+    }
+
+    /// Whether `key` comes before the start of the range, a descending scan can stop there.
+    pub fn starts_after(&self, key: u32) -> bool {
+        match self.start {
+            Bound::Included(start) => key < start,
+            Bound::Excluded(start) => key <= start,
+            Bound::Unbounded => false,
+        }
+    }
+}
+
+/// The order a `select` returns rows in, `order by id desc` scans backwards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScanOrder {
+    Ascending,
+    Descending,
+}
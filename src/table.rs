@@ -1,42 +1,64 @@
-use crate::constants::PAGE_SIZE;
+use crate::config::DbConfig;
+use crate::constants::{DEFAULT_ROOT_PAGE_NUM, HEADER_PAGE_NUM};
 use crate::pager::Pager;
 use crate::cursor::Cursor;
-use crate::enums::NodeType;
+use crate::enums::{DbError, JournalMode, NodeType, Synchronous};
+use crate::header::{page_count, set_page_count, FileHeader};
 use crate::node::{get_node_type, initialize_leaf_node, internal_node_child, internal_node_key, internal_node_num_keys, leaf_node_key, leaf_node_num_cells, set_node_root};
 
 pub struct Table {
     pub root_page_num: usize,
     pub pager: Box<Pager>,
+    pub file_name: String,
+    pub config: DbConfig,
 }
 
 impl Table {
-    pub fn open(file_name: &str) -> Table {
-        let mut pager = Pager::open(file_name);
-        let root_page_num = 0;
-        if pager.num_pages == 0 {
+    pub fn open(file_name: &str, config: &DbConfig) -> Result<Table, DbError> {
+        let mut pager = Pager::open(file_name, config)?;
+        let root_page_num = if pager.num_pages == 0 {
             // new database file
-            let root_node = pager.get_page(root_page_num);
+            let root_page_num = DEFAULT_ROOT_PAGE_NUM;
+            let root_node = pager.get_page(root_page_num)?;
             initialize_leaf_node(root_node);
             set_node_root(root_node, true);
-        }
-        Table {
+            let header = FileHeader::new(pager.page_size, root_page_num, pager.num_pages);
+            header.write(pager.get_page(HEADER_PAGE_NUM)?);
+            // written out straight away so the file can be reopened after a crash
+            pager.commit();
+            root_page_num
+        } else {
+            let header = FileHeader::read(pager.get_page_ref(HEADER_PAGE_NUM)?)?;
+            if header.page_count as usize > pager.num_pages {
+                return Err(DbError::InvalidFile(format!(
+                    "file is truncated: header records {} pages but only {} exist",
+                    header.page_count, pager.num_pages
+                )));
+            }
+            header.root_page_num as usize
+        };
+        Ok(Table {
             root_page_num,
             pager: Box::new(pager),
-        }
+            file_name: file_name.to_string(),
+            config: config.clone(),
+        })
     }
 
-    pub fn table_find(&mut self, key: u32) -> Cursor {
+    /// Cursor at `key`, or where it would be inserted. A page on the way down that
+    /// fails its checksum is returned as `DbError::CorruptPage`.
+    pub fn table_find(&mut self, key: u32) -> Result<Cursor<'_>, DbError> {
         let root_page_num = self.root_page_num;
-        let root_node = self.pager.get_page(root_page_num);
-        let node_type = get_node_type(root_node);
+        let root_node = self.pager.get_page_ref(root_page_num)?;
+        let node_type = get_node_type(root_node, root_page_num)?;
         match node_type {
             NodeType::NodeLeaf => self.leaf_node_find(root_page_num, key),
             NodeType::NodeInternal => self.internal_node_find(root_page_num, key),
         }
     }
 
-    pub fn leaf_node_find(&mut self, page_num: usize, key: u32) -> Cursor {
-        fn binary_search_leaf(root_node: &mut [u8], key: u32, num_cells: u32) -> usize {
+    pub fn leaf_node_find(&mut self, page_num: usize, key: u32) -> Result<Cursor<'_>, DbError> {
+        fn binary_search_leaf(root_node: &[u8], key: u32, num_cells: u32) -> usize {
             let mut min_index = 0;
             let mut one_past_max_index = num_cells as usize;
             while one_past_max_index != min_index {
@@ -55,18 +77,13 @@ impl Table {
             min_index
         }
 
-        let node = self.pager.get_page(page_num);
+        let node = self.pager.get_page_ref(page_num)?;
         let num_cells = leaf_node_num_cells(node);
         let cell_num = binary_search_leaf(node, key, num_cells);
-        Cursor {
-            table: self,
-            page_num,
-            cell_num,
-            end_of_table: false,
-        }
+        Cursor::new(self, page_num, cell_num, false)
     }
 
-    pub fn internal_node_find_child(&mut self, root_node: &mut [u8], key: u32, num_keys: u32) -> usize {
+    pub fn internal_node_find_child(&mut self, root_node: &[u8], key: u32, num_keys: u32) -> usize {
         let mut min_index = 0;
         let mut max_index = num_keys as usize;
         while max_index != min_index {
@@ -83,42 +100,122 @@ impl Table {
         min_index
     }
 
-    pub fn internal_node_find(&mut self, page_num: usize, key: u32) -> Cursor {
-        let node = self.pager.get_page(page_num);
-        let mut node_buffer = [0u8; PAGE_SIZE];
-        node_buffer.copy_from_slice(node);
-        let num_keys = internal_node_num_keys(&mut node_buffer);
-        let child_index = self.internal_node_find_child(&mut node_buffer, key, num_keys);
+    pub fn internal_node_find(&mut self, page_num: usize, key: u32) -> Result<Cursor<'_>, DbError> {
+        let node = self.pager.get_page_ref(page_num)?;
+        let mut node_buffer = node.to_vec();
+        let num_keys = internal_node_num_keys(&node_buffer);
+        let child_index = self.internal_node_find_child(&node_buffer, key, num_keys);
         let child_num = usize::from_le_bytes(
             internal_node_child(&mut node_buffer, child_index).try_into().unwrap(),
-        ) as usize;
-        let child_node = self.pager.get_page(child_num);
-        match get_node_type(child_node) {
+        );
+        let child_node = self.pager.get_page_ref(child_num)?;
+        match get_node_type(child_node, child_num)? {
             NodeType::NodeInternal => self.internal_node_find(child_num, key),
             NodeType::NodeLeaf => self.leaf_node_find(child_num, key),
         }
     }
 
-    pub fn table_start(&mut self) -> Cursor {
-        let page_num = self.get_page_num_for_key(0);
-        let node = self.pager.get_page(page_num);
-        let num_cells = leaf_node_num_cells(node);
-        let mut cursor = self.table_find(0);
-        cursor.end_of_table = num_cells == 0;
-        cursor
+    pub fn table_start(&mut self) -> Result<Cursor<'_>, DbError> {
+        self.seek_ge(0)
+    }
+
+    /// Cursor at the first key at or above `key`, at the end of the table when there is none.
+    pub fn seek_ge(&mut self, key: u32) -> Result<Cursor<'_>, DbError> {
+        // table_find stops past the last cell when every key in the leaf is smaller
+        let mut cursor = self.table_find(key)?;
+        cursor.skip_to_next_leaf()?;
+        Ok(cursor)
+    }
+
+    /// Cursor at the first key above `key`, at the end of the table when there is none.
+    pub fn seek_gt(&mut self, key: u32) -> Result<Cursor<'_>, DbError> {
+        let mut cursor = self.seek_ge(key)?;
+        if cursor.cursor_key()? == Some(key) {
+            cursor.advance()?;
+        }
+        Ok(cursor)
+    }
+
+    /// Cursor at the last row, at the end of the table when it is empty.
+    pub fn table_end(&mut self) -> Result<Cursor<'_>, DbError> {
+        self.seek_le(u32::MAX)
+    }
+
+    /// Cursor at the last key at or below `key`, at the end of the table when there is none.
+    pub fn seek_le(&mut self, key: u32) -> Result<Cursor<'_>, DbError> {
+        let mut cursor = self.seek_gt(key)?;
+        cursor.retreat()?;
+        Ok(cursor)
+    }
+
+    /// Cursor at the last key below `key`, at the end of the table when there is none.
+    pub fn seek_lt(&mut self, key: u32) -> Result<Cursor<'_>, DbError> {
+        let mut cursor = self.seek_ge(key)?;
+        cursor.retreat()?;
+        Ok(cursor)
+    }
+
+    pub fn set_journal_mode(&mut self, journal_mode: JournalMode) -> Result<(), DbError> {
+        self.pager.set_journal_mode(journal_mode)?;
+        self.config.journal_mode = journal_mode;
+        Ok(())
+    }
+
+    pub fn set_synchronous(&mut self, synchronous: Synchronous) {
+        self.pager.set_synchronous(synchronous);
+        self.config.synchronous = synchronous;
+    }
+
+    /// Runs a statement under a savepoint of its own, outside a transaction it is
+    /// committed on its own. A statement that fails part way, on a page it cannot
+    /// read, is undone so the table is left as the statement found it.
+    pub fn run_statement<T>(
+        &mut self,
+        statement: impl FnOnce(&mut Table) -> Result<T, DbError>,
+    ) -> Result<T, DbError> {
+        let in_transaction = self.pager.in_transaction();
+        if in_transaction {
+            self.pager.begin_statement();
+        } else {
+            self.pager.begin();
+        }
+        let result = statement(self);
+        match (in_transaction, result.is_ok()) {
+            (true, succeeded) => self.pager.end_statement(succeeded),
+            (false, true) => self.commit(),
+            (false, false) => self.pager.rollback(),
+        }
+        result
+    }
+
+    /// Makes the changes of the statements run so far durable.
+    pub fn commit(&mut self) {
+        self.write_header();
+        self.pager.commit();
     }
 
     pub fn db_close(&mut self) {
-        for i in 0..self.pager.num_pages {
-            if self.pager.pages[i].is_none() {
-                continue;
-            }
-            self.pager.pager_flush(i);
+        // closing abandons a transaction that was never committed
+        if self.pager.in_transaction() {
+            self.pager.rollback();
         }
+        self.commit();
+        self.pager.checkpoint();
     }
 
-    fn get_page_num_for_key(&mut self, key: u32) -> usize {
-        let cursor = self.table_find(key);
-        cursor.page_num
+    // the header page is only dirtied when the page count changed
+    fn write_header(&mut self) {
+        let num_pages = self.pager.num_pages;
+        // the header was checked when the table was opened and every write since
+        // recomputed its checksum, so a failed read here means the file was changed
+        // under us
+        let header = self.pager.get_page_ref(HEADER_PAGE_NUM).unwrap_or_else(|err| {
+            eprintln!("Error reading header page: {}", err);
+            panic!("Error reading header page.");
+        });
+        if page_count(header) != num_pages {
+            // cached by the read above
+            set_page_count(self.pager.get_page(HEADER_PAGE_NUM).unwrap(), num_pages);
+        }
     }
 }
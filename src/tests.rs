@@ -1,40 +1,56 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
+    use std::ops::{Bound, RangeBounds};
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use rand::seq::SliceRandom;
     use pretty_assertions::assert_eq;
     use crate::{
-        enums::ExecuteResult,
+        bulk_load,
+        check,
+        checksum,
+        config::DbConfig,
+        constants::*,
+        enums::{DbError, ExecuteResult, JournalMode, NodeType, PrepareResult, Synchronous},
         executor,
+        header::FileHeader,
+        inspect,
+        node,
+        node::{
+            get_node_type, internal_node_child, internal_node_key, internal_node_num_keys, leaf_node_cell_mut,
+            leaf_node_next_leaf, leaf_node_num_cells,
+        },
+        pager::Pager,
+        parser,
         row::Row,
-        statement::{Statement, StatementType},
+        statement::{KeyRange, RowUpdate, ScanOrder, Statement, StatementType},
         table::Table,
+        vacuum,
     };
 
-    const DEFAULT_DB_FILE_PATH: &str = "test.db";
-
     // Table tests
     #[test]
     fn table_open() {
         let table = open_table();
-        assert_eq!(table.root_page_num, 0, "root_page_num should be 0.");
-        assert_eq!(table.pager.num_pages, 1, "num_pages should be 1.");
+        assert_eq!(table.root_page_num, 1, "root_page_num should be 1.");
+        assert_eq!(table.pager.num_pages, 2, "num_pages should be 2.");
     }
 
     #[test]
     fn table_find() {
         let mut table = open_table();
-        let cursor = table.table_find(0);
+        let cursor = table.table_find(0).unwrap();
         assert_eq!(cursor.cell_num, 0, "cell_num should be 0.");
-        assert_eq!(cursor.page_num, 0, "page_num should be 0.");
+        assert_eq!(cursor.page_num, 1, "page_num should be 1.");
         assert_eq!(cursor.end_of_table, false, "end_of_table should be false.");
     }
 
     #[test]
     fn table_start() {
         let mut table = open_table();
-        let cursor = table.table_start();
+        let cursor = table.table_start().unwrap();
         assert_eq!(cursor.cell_num, 0, "cell_num should be 0.");
-        assert_eq!(cursor.page_num, 0, "page_num should be 0.");
+        assert_eq!(cursor.page_num, 1, "page_num should be 1.");
         assert_eq!(cursor.end_of_table, true, "end_of_table should be false.");
     }
 
@@ -54,7 +70,7 @@ mod tests {
         let ( _, _) = insert_row(&mut table);
         let statement = Statement {
             row_to_insert: None,
-            statement_type: StatementType::StatementSelect,
+            statement_type: StatementType::StatementSelect(KeyRange::all(), ScanOrder::Ascending),
         };
         let execute_result = executor::execute_statement(&statement, &mut table);
         match execute_result {
@@ -67,11 +83,11 @@ mod tests {
     fn test_inserted_data() {
         let mut table = open_table();
         let ( _, inserted_row) = insert_row(&mut table);
-        let mut cursor = table.table_start();
+        let mut cursor = table.table_start().unwrap();
         // the count of rows in the table should be 1
         assert_eq!(cursor.end_of_table, false, "end_of_table should be false.");
-        let row = Row::deserialize_row(cursor.cursor_value());
-        cursor.advance();
+        let row = Row::deserialize_row(&cursor.cursor_value().unwrap());
+        cursor.advance().unwrap();
         assert_eq!(row, inserted_row, "row should match inserted row.");
         assert_eq!(cursor.end_of_table, true, "end_of_table should be true.");
     }
@@ -80,7 +96,7 @@ mod tests {
     fn test_node_splitting() {
         let mut table = open_table();
         let mut inserted_rows: Vec<Row> = Vec::new();
-        for i in 0..crate::constants::LEAF_NODE_MAX_CELLS + 1 {
+        for i in 0..leaf_node_max_cells(DEFAULT_PAGE_SIZE) + 1 {
             let row_to_insert = Row {
                 id: (i + 1) as i32,
                 username: "test".to_string(),
@@ -96,7 +112,7 @@ mod tests {
 
         let statement = Statement {
             row_to_insert: None,
-            statement_type: StatementType::StatementSelect,
+            statement_type: StatementType::StatementSelect(KeyRange::all(), ScanOrder::Ascending),
         };
         let execute_result = executor::execute_statement(&statement, &mut table);
         match execute_result {
@@ -111,7 +127,7 @@ mod tests {
     fn test_node_splitting_insert_beginning() {
         let mut table = open_table();
         let mut inserted_rows: Vec<Row> = Vec::new();
-        for i in (0..crate::constants::LEAF_NODE_MAX_CELLS + 1).rev() {
+        for i in (0..leaf_node_max_cells(DEFAULT_PAGE_SIZE) + 1).rev() {
             let row_to_insert = Row {
                 id: (i + 1) as i32,
                 username: "test".to_string(),
@@ -126,11 +142,11 @@ mod tests {
         }
 
         // sort the inserted rows in ascending order
-        inserted_rows.sort_by(|a, b| a.id.cmp(&b.id));
+        inserted_rows.sort_by_key(|row| row.id);
 
         let statement = Statement {
             row_to_insert: None,
-            statement_type: StatementType::StatementSelect,
+            statement_type: StatementType::StatementSelect(KeyRange::all(), ScanOrder::Ascending),
         };
         let execute_result = executor::execute_statement(&statement, &mut table);
         match execute_result {
@@ -148,7 +164,7 @@ mod tests {
 
         // randomly pick a value from 0 to LEAF_NODE_MAX_CELLS, LEAF_NODE_MAX_CELLS times
         let mut rng = rand::thread_rng();
-        let mut random_values: Vec<usize> = (1..crate::constants::LEAF_NODE_MAX_CELLS + 2).collect();
+        let mut random_values: Vec<usize> = (1..leaf_node_max_cells(DEFAULT_PAGE_SIZE) + 2).collect();
         random_values.shuffle(&mut rng);
 
         for key in random_values {
@@ -166,11 +182,11 @@ mod tests {
         }
 
         // sort the inserted rows in ascending order
-        inserted_rows.sort_by(|a, b| a.id.cmp(&b.id));
+        inserted_rows.sort_by_key(|row| row.id);
         
         let statement = Statement {
             row_to_insert: None,
-            statement_type: StatementType::StatementSelect,
+            statement_type: StatementType::StatementSelect(KeyRange::all(), ScanOrder::Ascending),
         };
         let execute_result = executor::execute_statement(&statement, &mut table);
         match execute_result {
@@ -185,7 +201,7 @@ mod tests {
     fn test_insert_after_splitting() {
         let mut table = open_table();
         let mut inserted_rows: Vec<Row> = Vec::new();
-        for i in 0..(crate::constants::LEAF_NODE_MAX_CELLS + 3) {
+        for i in 0..(leaf_node_max_cells(DEFAULT_PAGE_SIZE) + 3) {
             let row_to_insert = Row {
                 id: (i + 1) as i32,
                 username: "test".to_string(),
@@ -201,7 +217,7 @@ mod tests {
 
         let statement = Statement {
             row_to_insert: None,
-            statement_type: StatementType::StatementSelect,
+            statement_type: StatementType::StatementSelect(KeyRange::all(), ScanOrder::Ascending),
         };
         let execute_result = executor::execute_statement(&statement, &mut table);
         match execute_result {
@@ -216,7 +232,7 @@ mod tests {
     fn test_insert_many_rows() {
         let mut table = open_table();
         let mut inserted_rows: Vec<Row> = Vec::new();
-        for i in 0..(crate::constants::LEAF_NODE_MAX_CELLS*10) {
+        for i in 0..(leaf_node_max_cells(DEFAULT_PAGE_SIZE)*10) {
             let row_to_insert = Row {
                 id: (i + 1) as i32,
                 username: "test".to_string(),
@@ -232,7 +248,7 @@ mod tests {
 
         let statement = Statement {
             row_to_insert: None,
-            statement_type: StatementType::StatementSelect,
+            statement_type: StatementType::StatementSelect(KeyRange::all(), ScanOrder::Ascending),
         };
         let execute_result = executor::execute_statement(&statement, &mut table);
         match execute_result {
@@ -243,19 +259,2515 @@ mod tests {
         compare_data(&mut table, inserted_rows);
     }
 
-    // Helper functions
+    // Buffer pool tests
+    #[test]
+    fn test_table_grows_past_buffer_pool() {
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            pool_size: 4,
+            ..DbConfig::default()
+        };
+        let mut table = open_table_with_config(&config);
+        let inserted_rows = insert_sequential_rows(&mut table, 1500);
+        assert!(table.pager.num_pages > 100, "table should grow past 100 pages.");
+        compare_data(&mut table, inserted_rows);
+    }
 
-    fn open_table() -> Table {
-        Table::open(DEFAULT_DB_FILE_PATH)
+    #[test]
+    fn test_small_buffer_pool_persists_rows() {
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            pool_size: 3,
+            ..DbConfig::default()
+        };
+        let db_file_path = test_db_file_path();
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        let inserted_rows = insert_sequential_rows(&mut table, 100);
+        table.db_close();
+
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_pinned_page_is_not_evicted() {
+        let mut pager = Pager::open(&test_db_file_path(), &pool_config(2)).unwrap();
+        pager.pin_page(0).unwrap();
+        for page_num in 1..5 {
+            pager.get_page(page_num).unwrap();
+        }
+        assert!(pager.cached_pages().contains(&0), "pinned page should stay cached.");
+        pager.unpin_page(0);
+        for page_num in 5..7 {
+            pager.get_page(page_num).unwrap();
+        }
+        assert!(!pager.cached_pages().contains(&0), "unpinned page should be evicted.");
+    }
+
+    #[test]
+    #[should_panic(expected = "All buffer pool frames are pinned.")]
+    fn test_all_frames_pinned() {
+        let mut pager = Pager::open(&test_db_file_path(), &pool_config(2)).unwrap();
+        pager.pin_page(0).unwrap();
+        pager.pin_page(1).unwrap();
+        pager.get_page(2).unwrap();
+    }
+
+    #[test]
+    fn test_pool_too_small_for_a_split_is_rejected() {
+        for pool_size in [0, 1] {
+            assert!(
+                matches!(Pager::open(&test_db_file_path(), &pool_config(pool_size)), Err(DbError::InvalidArgument(_))),
+                "pool size {} should be rejected.",
+                pool_size
+            );
+        }
+    }
+
+    #[test]
+    fn test_smallest_buffer_pool_splits_leaves() {
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            pool_size: MIN_POOL_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = open_table_with_config(&config);
+        let inserted_rows = insert_sequential_rows(&mut table, 300);
+        assert!(tree_depth(&mut table) > 2, "tree should grow past one internal level.");
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_reads_do_not_write_pages() {
+        let db_file_path = test_db_file_path();
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            pool_size: 4,
+            ..DbConfig::default()
+        };
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        let inserted_rows = insert_sequential_rows(&mut table, 300);
+        let page_writes = table.pager.page_writes();
+        // scanning evicts every leaf at least once with a pool this small
+        compare_data(&mut table, inserted_rows);
+        table.table_find(150).unwrap();
+        table.commit();
+        assert_eq!(table.pager.page_writes(), page_writes, "clean pages should not be written.");
+    }
+
+    #[test]
+    fn test_commit_writes_only_changed_pages() {
+        let db_file_path = test_db_file_path();
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        insert_sequential_rows(&mut table, 300);
+        let page_writes = table.pager.page_writes();
+        assert!(matches!(update_row(&mut table, 150, Some("changed"), None), ExecuteResult::ExecuteSuccess));
+        assert_eq!(table.pager.page_writes(), page_writes + 1, "only the updated leaf should be written.");
+        table.db_close();
+        assert_eq!(table.pager.page_writes(), page_writes + 1, "closing should not write clean pages.");
+    }
+
+    #[test]
+    fn test_empty_free_list_leaves_header_clean() {
+        let mut table = open_table_with_config(&DbConfig::default());
+        insert_sequential_rows(&mut table, 10);
+        table.commit();
+        let page_writes = table.pager.page_writes();
+        let num_pages = table.pager.num_pages;
+        assert_eq!(table.pager.get_unused_page_num().unwrap(), num_pages);
+        table.commit();
+        assert_eq!(table.pager.page_writes(), page_writes, "the header should not be written.");
+    }
+
+    // Page size tests
+    #[test]
+    fn test_page_size_kept_from_header() {
+        let db_file_path = test_db_file_path();
+        let config = DbConfig {
+            page_size: 1024,
+            ..DbConfig::default()
+        };
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        let inserted_rows = insert_sequential_rows(&mut table, 50);
+        table.db_close();
+
+        // the page size flag only applies to new files
+        let config = DbConfig {
+            page_size: 8192,
+            ..DbConfig::default()
+        };
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        assert_eq!(table.pager.page_size, 1024, "page size should come from the header.");
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_new_file_rejects_invalid_page_size() {
+        for page_size in [256, 1000, 128 * 1024] {
+            let config = DbConfig {
+                page_size,
+                ..DbConfig::default()
+            };
+            assert!(Table::open(&test_db_file_path(), &config).is_err());
+        }
+    }
+
+    #[test]
+    fn test_insert_with_page_sizes() {
+        let mut rng = rand::thread_rng();
+        for page_size in [MIN_PAGE_SIZE, 1024, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE] {
+            let config = DbConfig {
+                page_size,
+                ..DbConfig::default()
+            };
+            let mut table = open_table_with_config(&config);
+            let mut keys: Vec<usize> = (1..leaf_node_max_cells(page_size) * 3 + 2).collect();
+            keys.shuffle(&mut rng);
+            let mut inserted_rows: Vec<Row> = Vec::new();
+            for key in keys {
+                let row_to_insert = Row {
+                    id: key as i32,
+                    username: "test".to_string(),
+                    email: "test@test.com".to_string(),
+                };
+                insert_row_internal(&mut table, &row_to_insert);
+                inserted_rows.push(row_to_insert);
+            }
+            inserted_rows.sort_by_key(|row| row.id);
+            compare_data(&mut table, inserted_rows);
+        }
+    }
+
+    // Internal node fanout tests
+    #[test]
+    fn test_internal_node_fanout_from_page_size() {
+        assert_eq!(internal_node_max_cells(MIN_PAGE_SIZE), 40);
+        assert_eq!(internal_node_max_cells(DEFAULT_PAGE_SIZE), 339);
+        assert!(internal_node_max_cells(8192) >= 500);
+    }
+
+    #[test]
+    fn test_internal_node_split_at_realistic_fanout() {
+        let page_size = 8192;
+        let config = DbConfig {
+            page_size,
+            ..DbConfig::default()
+        };
+        let mut table = open_table_with_config(&config);
+        // sequential inserts leave every full leaf about half full, keep adding
+        // rows until the root has overflowed once
+        let mut inserted_rows = Vec::new();
+        while tree_depth(&mut table) < 3 {
+            inserted_rows.extend(test_rows(inserted_rows.len() as i32 + 1..inserted_rows.len() as i32 + 101));
+            for row in &inserted_rows[inserted_rows.len() - 100..] {
+                insert_row_internal(&mut table, row);
+            }
+        }
+
+        assert_eq!(tree_depth(&mut table), 3, "root should have split into internal nodes.");
+        let root = table.pager.get_page_ref(table.root_page_num).unwrap().to_vec();
+        for child_num in 0..=internal_node_num_keys(&root) as usize {
+            let child_page_num = internal_node_child_page_num(&root, child_num);
+            let child = table.pager.get_page_ref(child_page_num).unwrap();
+            assert!(
+                internal_node_num_keys(child) as usize >= internal_node_max_cells(page_size) / 2 - 1,
+                "split internal nodes should be about half full."
+            );
+        }
+        assert_tree_is_valid(&mut table);
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_internal_node_split_random_order() {
+        let config = DbConfig::default();
+        let mut table = open_table_with_config(&config);
+        // no leaf holds more rows than the first ones that fill a leaf, so this
+        // makes more leaves than a single root can point to
+        let mut keys: Vec<usize> = (1..=internal_node_max_cells(DEFAULT_PAGE_SIZE)
+            * rows_per_leaf(DEFAULT_PAGE_SIZE))
+            .collect();
+        keys.shuffle(&mut rand::thread_rng());
+        let mut inserted_rows: Vec<Row> = Vec::new();
+        for key in keys {
+            let row_to_insert = Row {
+                id: key as i32,
+                username: format!("user{}", key),
+                email: format!("user{}@test.com", key),
+            };
+            insert_row_internal(&mut table, &row_to_insert);
+            inserted_rows.push(row_to_insert);
+        }
+        inserted_rows.sort_by_key(|row| row.id);
+
+        assert_eq!(tree_depth(&mut table), 3, "root should have split into internal nodes.");
+        assert_tree_is_valid(&mut table);
+        compare_data(&mut table, inserted_rows);
+    }
+
+    // Duplicate key tests
+    #[test]
+    fn test_duplicate_key_rejected() {
+        let mut table = open_table();
+        let inserted_rows = insert_sequential_rows(&mut table, 5);
+        let pages_before = page_snapshot(&mut table);
+        let duplicate = Row {
+            id: 3,
+            username: "other".to_string(),
+            email: "other@test.com".to_string(),
+        };
+        match insert_row_internal(&mut table, &duplicate).0 {
+            ExecuteResult::ExecuteTableDuplicateKey => (),
+            _ => panic!("inserting an existing key should be rejected."),
+        }
+        assert_eq!(page_snapshot(&mut table), pages_before, "a rejected insert should not touch any page.");
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_duplicate_key_in_full_leaf_rejected() {
+        let mut table = open_table();
+        let inserted_rows = insert_sequential_rows(&mut table, rows_per_leaf(DEFAULT_PAGE_SIZE));
+        let pages_before = page_snapshot(&mut table);
+        for row in [&inserted_rows[0], &inserted_rows[6], inserted_rows.last().unwrap()] {
+            match insert_row_internal(&mut table, row).0 {
+                ExecuteResult::ExecuteTableDuplicateKey => (),
+                _ => panic!("inserting an existing key into a full leaf should be rejected."),
+            }
+        }
+        assert_eq!(page_snapshot(&mut table), pages_before, "a rejected insert should not split the leaf.");
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_duplicate_keys_in_multi_level_tree_rejected() {
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = open_table_with_config(&config);
+        let mut inserted_rows = insert_shuffled_rows(&mut table, 1000);
+        assert!(tree_depth(&mut table) >= 3, "tree should have several levels.");
+        let pages_before = page_snapshot(&mut table);
+        inserted_rows.shuffle(&mut rand::thread_rng());
+        for row in &inserted_rows {
+            match insert_row_internal(&mut table, row).0 {
+                ExecuteResult::ExecuteTableDuplicateKey => (),
+                _ => panic!("inserting existing key {} should be rejected.", row.id),
+            }
+        }
+        assert_eq!(page_snapshot(&mut table), pages_before, "rejected inserts should not touch any page.");
+        inserted_rows.sort_by_key(|row| row.id);
+        compare_data(&mut table, inserted_rows);
+    }
+
+    // Delete tests
+    #[test]
+    fn test_delete_row() {
+        let mut table = open_table();
+        insert_row(&mut table);
+        match delete_row(&mut table, 1) {
+            ExecuteResult::ExecuteSuccess => (),
+            _ => panic!("Error executing statement."),
+        }
+        let cursor = table.table_start().unwrap();
+        assert!(cursor.end_of_table, "table should be empty after the delete.");
+    }
+
+    #[test]
+    fn test_delete_missing_row() {
+        let mut table = open_table();
+        let inserted_rows = insert_sequential_rows(&mut table, 10);
+        for id in [0, 11, 100] {
+            match delete_row(&mut table, id) {
+                ExecuteResult::ExecuteRecordNotFound => (),
+                _ => panic!("deleting a missing row should report it was not found."),
+            }
+        }
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_prepare_delete() {
+        match parser::prepare_statement("delete 42") {
+            PrepareResult::PrepareSuccess(Statement {
+                statement_type: StatementType::StatementDelete(42),
+                ..
+            }) => (),
+            _ => panic!("'delete 42' should parse."),
+        }
+        assert!(matches!(parser::prepare_statement("delete -1"), PrepareResult::PrepareNegativeId));
+        assert!(matches!(parser::prepare_statement("delete"), PrepareResult::PrepareSyntaxError));
+        assert!(matches!(parser::prepare_statement("delete a"), PrepareResult::PrepareSyntaxError));
+    }
+
+    #[test]
+    fn test_delete_all_rows_random_order() {
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = open_table_with_config(&config);
+        let mut rng = rand::thread_rng();
+        let mut remaining_rows = insert_shuffled_rows(&mut table, 3000);
+        assert!(tree_depth(&mut table) >= 3, "tree should have several levels.");
+
+        let mut delete_order: Vec<i32> = remaining_rows.iter().map(|row| row.id).collect();
+        delete_order.shuffle(&mut rng);
+        for (deleted, id) in delete_order.into_iter().enumerate() {
+            match delete_row(&mut table, id as u32) {
+                ExecuteResult::ExecuteSuccess => (),
+                _ => panic!("Error deleting row {}.", id),
+            }
+            remaining_rows.retain(|row| row.id != id);
+            if deleted % 250 == 0 {
+                compare_data(&mut table, remaining_rows.clone());
+                assert_tree_is_valid(&mut table);
+            }
+        }
+        assert_eq!(tree_depth(&mut table), 1, "root should collapse back into a leaf.");
+        assert!(table.table_start().unwrap().end_of_table, "table should be empty.");
+
+        // the emptied tree still accepts rows
+        let inserted_rows = insert_shuffled_rows(&mut table, 500);
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_delete_then_reinsert() {
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = open_table_with_config(&config);
+        let inserted_rows = insert_shuffled_rows(&mut table, 2000);
+
+        // delete every other row, then every row in a contiguous range
+        for id in (1..=2000).step_by(2).chain(1000..1500) {
+            delete_row(&mut table, id);
+        }
+        let remaining_rows: Vec<Row> = inserted_rows
+            .iter()
+            .filter(|row| row.id % 2 == 0 && !(1000..1500).contains(&row.id))
+            .cloned()
+            .collect();
+        compare_data(&mut table, remaining_rows);
+
+        for row in inserted_rows.iter().filter(|row| row.id % 2 == 1 || (1000..1500).contains(&row.id)) {
+            insert_row_internal(&mut table, row);
+        }
+        compare_data(&mut table, inserted_rows);
+    }
+
+    // Update tests
+    #[test]
+    fn test_update_row() {
+        let mut table = open_table();
+        let mut inserted_rows = insert_sequential_rows(&mut table, 10);
+        match update_row(&mut table, 4, Some("alice"), Some("alice@test.com")) {
+            ExecuteResult::ExecuteSuccess => (),
+            _ => panic!("Error executing statement."),
+        }
+        inserted_rows[3].username = "alice".to_string();
+        inserted_rows[3].email = "alice@test.com".to_string();
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_update_single_column() {
+        let mut table = open_table();
+        let mut inserted_rows = insert_sequential_rows(&mut table, 3);
+        update_row(&mut table, 1, Some("bob"), None);
+        update_row(&mut table, 2, None, Some("carol@test.com"));
+        inserted_rows[0].username = "bob".to_string();
+        inserted_rows[1].email = "carol@test.com".to_string();
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_update_missing_row() {
+        let mut table = open_table();
+        let inserted_rows = insert_sequential_rows(&mut table, 10);
+        match update_row(&mut table, 11, Some("nobody"), None) {
+            ExecuteResult::ExecuteRecordNotFound => (),
+            _ => panic!("updating a missing row should report it was not found."),
+        }
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_update_persists() {
+        let path = test_db_file_path();
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = Table::open(&path, &config).unwrap();
+        let mut inserted_rows = insert_sequential_rows(&mut table, 200);
+        for row in inserted_rows.iter_mut().step_by(7) {
+            row.username = format!("new{}", row.id);
+            update_row(&mut table, row.id as u32, Some(&row.username), None);
+        }
+        table.db_close();
+
+        let mut table = Table::open(&path, &config).unwrap();
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_prepare_update() {
+        match parser::prepare_statement("update 3 set email=x@y.com username=x") {
+            PrepareResult::PrepareSuccess(Statement {
+                statement_type: StatementType::StatementUpdate(3, update),
+                ..
+            }) => {
+                assert_eq!(update.username.as_deref(), Some("x"));
+                assert_eq!(update.email.as_deref(), Some("x@y.com"));
+            }
+            _ => panic!("update should parse."),
+        }
+        assert!(matches!(parser::prepare_statement("update -3 set username=x"), PrepareResult::PrepareNegativeId));
+        for input in [
+            "update 3",
+            "update 3 set",
+            "update 3 username=x",
+            "update 3 set name=x",
+            "update 3 set username",
+            "update 3 set username=x username=y",
+            "update x set username=x",
+        ] {
+            assert!(
+                matches!(parser::prepare_statement(input), PrepareResult::PrepareSyntaxError),
+                "'{}' should be a syntax error.",
+                input
+            );
+        }
+    }
+
+    // Variable-length row tests
+    #[test]
+    fn test_short_rows_share_a_leaf() {
+        let mut table = open_table();
+        let inserted_rows = insert_sequential_rows(&mut table, rows_per_leaf(DEFAULT_PAGE_SIZE));
+        // fixed-size rows with room for the longest username and email only fit 13 to a page
+        assert!(inserted_rows.len() > 100, "only {} rows fit in a leaf", inserted_rows.len());
+        assert_eq!(tree_depth(&mut table), 1, "the rows should all fit in the root leaf.");
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_long_rows_persist() {
+        let path = test_db_file_path();
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let max_email_len = leaf_node_max_local_payload(MIN_PAGE_SIZE) - ROW_MIN_SIZE - "user100".len();
+        let mut rows: Vec<Row> = (1..=100)
+            .map(|id| Row {
+                id,
+                username: format!("user{}", id),
+                email: "e".repeat(id as usize * 7 % (max_email_len + 1)),
+            })
+            .collect();
+        let mut table = Table::open(&path, &config).unwrap();
+        rows.shuffle(&mut rand::thread_rng());
+        for row in &rows {
+            assert!(matches!(insert_row_internal(&mut table, row).0, ExecuteResult::ExecuteSuccess));
+        }
+        assert_tree_is_valid(&mut table);
+        table.db_close();
+
+        let mut table = Table::open(&path, &config).unwrap();
+        rows.sort_by_key(|row| row.id);
+        compare_data(&mut table, rows);
+    }
+
+    #[test]
+    fn test_row_too_large_rejected() {
+        let mut table = open_table();
+        let inserted_rows = insert_sequential_rows(&mut table, 3);
+        // rows spill to overflow pages, only a field too long for its length is refused
+        let too_large = Row {
+            id: 4,
+            username: "user4".to_string(),
+            email: "e".repeat(MAX_FIELD_SIZE + 1),
+        };
+        let pages_before = page_snapshot(&mut table);
+        assert!(matches!(insert_row_internal(&mut table, &too_large).0, ExecuteResult::ExecuteRowTooLarge));
+        assert!(matches!(
+            update_row(&mut table, 1, None, Some(&too_large.email)),
+            ExecuteResult::ExecuteRowTooLarge
+        ));
+        assert_eq!(page_snapshot(&mut table), pages_before, "a rejected row should not touch any page.");
+        assert!(bulk_load::bulk_load(&mut open_table(), &[too_large], DEFAULT_FILL_PERCENT).is_err());
+        compare_data(&mut table, inserted_rows.clone());
+
+        // a row of exactly the largest size is fine
+        let largest = Row {
+            id: 4,
+            username: "u".repeat(MAX_FIELD_SIZE),
+            email: "e".repeat(MAX_FIELD_SIZE),
+        };
+        assert!(matches!(insert_row_internal(&mut table, &largest).0, ExecuteResult::ExecuteSuccess));
+        compare_data(&mut table, [inserted_rows, vec![largest]].concat());
+    }
+
+    #[test]
+    fn test_update_grows_row_in_full_leaf() {
+        let mut table = open_table();
+        let mut inserted_rows = insert_sequential_rows(&mut table, rows_per_leaf(DEFAULT_PAGE_SIZE));
+        inserted_rows[4].email = "e".repeat(500);
+        assert!(matches!(
+            update_row(&mut table, 5, None, Some(&inserted_rows[4].email)),
+            ExecuteResult::ExecuteSuccess
+        ));
+        assert_eq!(tree_depth(&mut table), 2, "the grown row should have split the leaf.");
+        assert_tree_is_valid(&mut table);
+        compare_data(&mut table, inserted_rows.clone());
+
+        // shrinking it back leaves the tree valid too
+        inserted_rows[4].email = String::new();
+        update_row(&mut table, 5, None, Some(""));
+        assert_tree_is_valid(&mut table);
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_fragmented_leaf_is_defragmented() {
+        let mut table = open_table();
+        let mut inserted_rows = insert_sequential_rows(&mut table, rows_per_leaf(DEFAULT_PAGE_SIZE));
+        for id in 10..20 {
+            delete_row(&mut table, id);
+        }
+        inserted_rows.retain(|row| !(10..20).contains(&row.id));
+        let root = table.pager.get_page_ref(table.root_page_num).unwrap();
+        let fragmented_bytes = node::leaf_node_fragmented_bytes(root);
+        assert!(fragmented_bytes > 0, "deleting cells below the content start should fragment the leaf.");
+
+        // a row taking all the free space only fits once the freed cells are reclaimed
+        let free_space = node::leaf_node_free_space(root);
+        let id = inserted_rows.len() as i32 + 100;
+        let username = format!("user{}", id);
+        let row = Row {
+            id,
+            email: "e".repeat(
+                free_space - LEAF_NODE_SLOT_SIZE - LEAF_NODE_PAYLOAD_SIZE_SIZE - ROW_MIN_SIZE - username.len(),
+            ),
+            username,
+        };
+        assert!(matches!(insert_row_internal(&mut table, &row).0, ExecuteResult::ExecuteSuccess));
+        assert_eq!(tree_depth(&mut table), 1, "the row should fit without a split.");
+        let root = table.pager.get_page_ref(table.root_page_num).unwrap();
+        assert_eq!(node::leaf_node_fragmented_bytes(root), 0);
+        assert_eq!(node::leaf_node_free_space(root), 0);
+        inserted_rows.push(row);
+        assert_tree_is_valid(&mut table);
+        compare_data(&mut table, inserted_rows);
+    }
+
+    // Overflow page tests
+    #[test]
+    fn test_large_rows_spill_to_overflow_pages() {
+        let path = test_db_file_path();
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = Table::open(&path, &config).unwrap();
+        let mut rows = test_rows(1..101);
+        for row in rows.iter_mut().step_by(3) {
+            row.email = format!("{}@test.com", "n".repeat(row.id as usize * 40));
+        }
+        let mut shuffled = rows.clone();
+        shuffled.shuffle(&mut rand::thread_rng());
+        for row in &shuffled {
+            assert!(matches!(insert_row_internal(&mut table, row).0, ExecuteResult::ExecuteSuccess));
+        }
+        assert!(!overflow_pages(&mut table).is_empty(), "large rows should have spilled.");
+        assert_tree_is_valid(&mut table);
+        compare_data(&mut table, rows.clone());
+        table.db_close();
+
+        let mut table = Table::open(&path, &config).unwrap();
+        compare_data(&mut table, rows.clone());
+        let range = KeyRange {
+            start: Bound::Included(50),
+            end: Bound::Included(60),
+        };
+        let mut scanned = Vec::new();
+        executor::scan_range(&mut table, &range, ScanOrder::Descending, |row| scanned.push(row.clone())).unwrap();
+        let expected: Vec<Row> = rows[49..60].iter().rev().cloned().collect();
+        assert_eq!(scanned, expected);
+    }
+
+    #[test]
+    fn test_delete_frees_overflow_chain() {
+        let mut table = open_table();
+        let rows = insert_sequential_rows(&mut table, 3);
+        let large = Row {
+            id: 4,
+            username: "user4".to_string(),
+            email: "e".repeat(DEFAULT_PAGE_SIZE * 3),
+        };
+        insert_row_internal(&mut table, &large);
+        let chain = overflow_pages(&mut table);
+        assert_eq!(chain.len(), overflow_chain_length(&large, DEFAULT_PAGE_SIZE));
+        let num_pages = table.pager.num_pages;
+
+        delete_row(&mut table, 4);
+        assert!(overflow_pages(&mut table).is_empty());
+        let mut free_list = table.pager.free_list().unwrap();
+        free_list.sort();
+        assert_eq!(free_list, chain, "the chain should be on the free list.");
+        assert_tree_is_valid(&mut table);
+        compare_data(&mut table, rows.clone());
+
+        // the freed pages are taken again before the file grows
+        insert_row_internal(&mut table, &large);
+        assert_eq!(table.pager.num_pages, num_pages);
+        assert!(table.pager.free_list().unwrap().is_empty());
+        assert_tree_is_valid(&mut table);
+        compare_data(&mut table, [rows, vec![large]].concat());
+    }
+
+    #[test]
+    fn test_update_frees_overflow_chain() {
+        let mut table = open_table();
+        let mut rows = insert_sequential_rows(&mut table, 10);
+        rows[4].email = "e".repeat(DEFAULT_PAGE_SIZE * 2);
+        update_row(&mut table, 5, None, Some(&rows[4].email));
+        assert_eq!(overflow_pages(&mut table).len(), overflow_chain_length(&rows[4], DEFAULT_PAGE_SIZE));
+        compare_data(&mut table, rows.clone());
+
+        // a longer row gets a new chain, the old one is freed first and reused
+        rows[4].email = "f".repeat(DEFAULT_PAGE_SIZE * 3);
+        update_row(&mut table, 5, None, Some(&rows[4].email));
+        let chain_length = overflow_chain_length(&rows[4], DEFAULT_PAGE_SIZE);
+        assert_eq!(overflow_pages(&mut table).len(), chain_length);
+        assert!(table.pager.free_list().unwrap().is_empty());
+        assert_tree_is_valid(&mut table);
+        compare_data(&mut table, rows.clone());
+
+        // only the username changes, the email is still reassembled and spilled again
+        rows[4].username = "alice".to_string();
+        update_row(&mut table, 5, Some("alice"), None);
+        compare_data(&mut table, rows.clone());
+
+        rows[4].email = "short@test.com".to_string();
+        update_row(&mut table, 5, None, Some(&rows[4].email));
+        assert!(overflow_pages(&mut table).is_empty());
+        assert_eq!(table.pager.free_list().unwrap().len(), chain_length);
+        assert_tree_is_valid(&mut table);
+        compare_data(&mut table, rows);
+    }
+
+    #[test]
+    fn test_vacuum_and_bulk_load_keep_large_rows() {
+        let db_file_path = test_db_file_path();
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        let mut rows = test_rows(1..201);
+        for row in rows.iter_mut().step_by(10) {
+            row.email = "e".repeat(1000 + row.id as usize);
+        }
+        let chain_lengths = |rows: &[Row]| -> usize {
+            rows.iter().map(|row| overflow_chain_length(row, MIN_PAGE_SIZE)).sum()
+        };
+        bulk_load::bulk_load(&mut table, &rows, DEFAULT_FILL_PERCENT).unwrap();
+        assert_eq!(overflow_pages(&mut table).len(), chain_lengths(&rows));
+        assert_tree_is_valid(&mut table);
+        compare_data(&mut table, rows.clone());
+
+        for id in (1..=200).step_by(20) {
+            delete_row(&mut table, id);
+        }
+        rows.retain(|row| (row.id - 1) % 20 != 0);
+        vacuum::vacuum(&mut table, DEFAULT_FILL_PERCENT).unwrap();
+        assert!(table.pager.free_list().unwrap().is_empty());
+        assert_eq!(overflow_pages(&mut table).len(), chain_lengths(&rows));
+        assert_tree_is_valid(&mut table);
+        compare_data(&mut table, rows);
+    }
+
+    #[test]
+    fn test_check_reports_broken_overflow_chain() {
+        let mut table = open_table();
+        let large = Row {
+            id: 1,
+            username: "user1".to_string(),
+            email: "e".repeat(DEFAULT_PAGE_SIZE * 3),
+        };
+        insert_row_internal(&mut table, &large);
+        let chain = overflow_pages(&mut table);
+        assert_eq!(chain.len(), 3);
+        let first_page = table.pager.get_page(chain[0]).unwrap();
+        first_page[OVERFLOW_PAGE_NEXT_OFFSET..OVERFLOW_PAGE_NEXT_OFFSET + OVERFLOW_PAGE_NEXT_SIZE]
+            .copy_from_slice(&0u32.to_le_bytes());
+        let spilled_size = large.serialized_size() - leaf_node_spilled_local_payload(DEFAULT_PAGE_SIZE);
+        assert_violations(
+            &mut table,
+            &[
+                &format!("page 1: cell 0 spills {} bytes to 1 overflow pages instead of 3", spilled_size),
+                &format!("page {}: page is neither in the tree nor on the free list", chain[1]),
+                &format!("page {}: page is neither in the tree nor on the free list", chain[2]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_delete_with_broken_overflow_chain_returns_error() {
+        let mut table = open_table();
+        let large = Row {
+            id: 1,
+            username: "user1".to_string(),
+            email: "e".repeat(DEFAULT_PAGE_SIZE * 3),
+        };
+        insert_row_internal(&mut table, &large);
+        let chain = overflow_pages(&mut table);
+        // the chain leads back into the tree, to the root leaf
+        let first_page = table.pager.get_page(chain[0]).unwrap();
+        first_page[OVERFLOW_PAGE_NEXT_OFFSET..OVERFLOW_PAGE_NEXT_OFFSET + OVERFLOW_PAGE_NEXT_SIZE]
+            .copy_from_slice(&(table.root_page_num as u32).to_le_bytes());
+        let delete = Statement {
+            row_to_insert: None,
+            statement_type: StatementType::StatementDelete(1),
+        };
+        match executor::execute_statement(&delete, &mut table) {
+            ExecuteResult::ExecuteError(DbError::CorruptPage(page_num)) => assert_eq!(page_num, table.root_page_num),
+            _ => panic!("the delete should stop where the chain leaves the overflow pages."),
+        }
+        assert!(table.pager.free_list().unwrap().is_empty(), "the freed overflow page should be taken back.");
+        assert_eq!(table.table_find(1).unwrap().cursor_key().unwrap(), Some(1));
+    }
+
+    #[test]
+    fn test_inspect_describes_overflow_pages() {
+        let mut table = open_table();
+        let large = Row {
+            id: 1,
+            username: "user1".to_string(),
+            email: "e".repeat(DEFAULT_PAGE_SIZE * 2),
+        };
+        insert_row_internal(&mut table, &large);
+        let chain = overflow_pages(&mut table);
+        assert_eq!(chain.len(), 2);
+        let db_file_path = table.file_name.clone();
+        table.db_close();
+
+        let mut page_file = inspect::PageFile::open(&db_file_path, None).unwrap();
+        let root = page_file.read_page(1).unwrap();
+        assert_eq!(
+            inspect::describe_cells(1, &root),
+            format!("  cell 0: key 1 ({} bytes, spills to page {})\n", large.serialized_size(), chain[0])
+        );
+        let first_page = page_file.read_page(chain[0]).unwrap();
+        assert_eq!(
+            inspect::describe_page(chain[0], &first_page),
+            format!("page {}: overflow\n  checksum: ok\n  next_overflow_page: {}\n", chain[0], chain[1])
+        );
+        let histogram = inspect::page_type_histogram(&mut page_file).unwrap();
+        assert!(histogram.contains("overflow: 2\n"), "{}", histogram);
+    }
+
+    // Free list tests
+    #[test]
+    fn test_deleted_pages_are_reused() {
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = open_table_with_config(&config);
+        assert!(table.pager.free_list().unwrap().is_empty(), "a new file should have no free pages.");
+        let inserted_rows = insert_sequential_rows(&mut table, 1000);
+        let num_pages = table.pager.num_pages;
+
+        for row in &inserted_rows {
+            delete_row(&mut table, row.id as u32);
+        }
+        // everything but the header and the root is free once the table is empty
+        let free_list = table.pager.free_list().unwrap();
+        assert_eq!(free_list.len(), num_pages - 2);
+        assert!(!free_list.contains(&HEADER_PAGE_NUM) && !free_list.contains(&table.root_page_num));
+
+        let inserted_rows = insert_sequential_rows(&mut table, 1000);
+        assert_eq!(table.pager.num_pages, num_pages, "the file should not grow while free pages remain.");
+        assert!(table.pager.free_list().unwrap().is_empty(), "every freed page should have been reused.");
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_free_list_persists() {
+        let db_file_path = test_db_file_path();
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        let inserted_rows = insert_sequential_rows(&mut table, 500);
+        for id in 1..=400 {
+            delete_row(&mut table, id);
+        }
+        let free_list = table.pager.free_list().unwrap();
+        assert!(!free_list.is_empty(), "deletes should have freed pages.");
+        table.db_close();
+
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        assert_eq!(table.pager.free_list().unwrap(), free_list);
+        let num_pages = table.pager.num_pages;
+        for row in &inserted_rows[..400] {
+            insert_row_internal(&mut table, row);
+        }
+        assert_eq!(table.pager.num_pages, num_pages, "the file should not grow while free pages remain.");
+        compare_data(&mut table, inserted_rows);
+    }
+
+    // Vacuum tests
+    #[test]
+    fn test_vacuum_reclaims_free_pages() {
+        let db_file_path = test_db_file_path();
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        let inserted_rows = insert_shuffled_rows(&mut table, 2000);
+        let mut ids: Vec<i32> = inserted_rows.iter().map(|row| row.id).collect();
+        ids.shuffle(&mut rand::thread_rng());
+        let (deleted_ids, kept_ids) = ids.split_at(1500);
+        for &id in deleted_ids {
+            delete_row(&mut table, id as u32);
+        }
+        let remaining_rows: Vec<Row> =
+            inserted_rows.into_iter().filter(|row| kept_ids.contains(&row.id)).collect();
+        let num_pages = table.pager.num_pages;
+
+        let bytes_reclaimed = vacuum::vacuum(&mut table, DEFAULT_FILL_PERCENT).unwrap();
+        assert_eq!(bytes_reclaimed, (num_pages - table.pager.num_pages) * MIN_PAGE_SIZE);
+        assert!(bytes_reclaimed > 0, "vacuum should shrink the file.");
+        assert!(table.pager.free_list().unwrap().is_empty(), "vacuum should leave no free pages.");
+        assert_tree_is_valid(&mut table);
+        let file_length = std::fs::metadata(&db_file_path).unwrap().len() as usize;
+        assert_eq!(file_length, table.pager.num_pages * MIN_PAGE_SIZE);
+        compare_data(&mut table, remaining_rows.clone());
+        table.db_close();
+
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        compare_data(&mut table, remaining_rows);
+        // the rebuilt tree still takes inserts and deletes
+        for &id in kept_ids {
+            delete_row(&mut table, id as u32);
+        }
+        let inserted_rows = insert_shuffled_rows(&mut table, 1000);
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_vacuum_fill_factor() {
+        let db_file_path = test_db_file_path();
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        let inserted_rows = insert_shuffled_rows(&mut table, 1000);
+        let space = leaf_node_space_for_cells(MIN_PAGE_SIZE);
+
+        vacuum::vacuum(&mut table, 100).unwrap();
+        let packed_leaves = leaf_used_spaces(&mut table);
+        assert_leaves_packed(&packed_leaves, space, inserted_rows.iter().max_by_key(|row| row.id).unwrap());
+        let packed_pages = table.pager.num_pages;
+        compare_data(&mut table, inserted_rows.clone());
+
+        vacuum::vacuum(&mut table, 50).unwrap();
+        let half_full_leaves = leaf_used_spaces(&mut table);
+        assert!(half_full_leaves.iter().all(|&used| used <= space / 2));
+        assert!(table.pager.num_pages > packed_pages, "half full leaves need more pages.");
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_vacuum_small_tables() {
+        let db_file_path = test_db_file_path();
+        let mut table = Table::open(&db_file_path, &DbConfig::default()).unwrap();
+        vacuum::vacuum(&mut table, DEFAULT_FILL_PERCENT).unwrap();
+        assert!(table.table_start().unwrap().end_of_table, "an empty table should stay empty.");
+        assert_eq!(table.pager.num_pages, 2);
+
+        let inserted_rows = insert_sequential_rows(&mut table, 5);
+        vacuum::vacuum(&mut table, DEFAULT_FILL_PERCENT).unwrap();
+        assert_eq!(tree_depth(&mut table), 1);
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_vacuum_rejects_bad_fill_factor() {
+        let db_file_path = test_db_file_path();
+        let mut table = Table::open(&db_file_path, &DbConfig::default()).unwrap();
+        let inserted_rows = insert_sequential_rows(&mut table, 5);
+        for fill_percent in [0, MIN_FILL_PERCENT - 1, 101] {
+            assert!(vacuum::vacuum(&mut table, fill_percent).is_err());
+        }
+        compare_data(&mut table, inserted_rows);
+    }
+
+    // Bulk load tests
+    #[test]
+    fn test_bulk_load_builds_valid_tree() {
+        let db_file_path = test_db_file_path();
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        let rows = test_rows(1..10_001);
+        assert_eq!(bulk_load::bulk_load(&mut table, &rows, 100).unwrap(), rows.len());
+        assert!(tree_depth(&mut table) > 2, "the load should build several internal levels.");
+        assert_tree_is_valid(&mut table);
+        assert_leaves_packed(&leaf_used_spaces(&mut table), leaf_node_space_for_cells(MIN_PAGE_SIZE), &rows[rows.len() - 1]);
+        table.db_close();
+
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        compare_data(&mut table, rows.clone());
+        let mut expected: Vec<u32> = (1..10_001).collect();
+        expected.reverse();
+        assert_eq!(scan_ids(&mut table, &KeyRange::all(), ScanOrder::Descending), expected);
+        // the loaded tree takes inserts and deletes like any other
+        for id in (2..10_001).step_by(2) {
+            delete_row(&mut table, id);
+        }
+        insert_rows_in_range(&mut table, 10_001..10_101);
+        assert_tree_is_valid(&mut table);
+    }
+
+    #[test]
+    fn test_bulk_load_fill_factor() {
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = open_table_with_config(&config);
+        let rows = test_rows(1..1001);
+        bulk_load::bulk_load(&mut table, &rows, 50).unwrap();
+        let space = leaf_node_space_for_cells(MIN_PAGE_SIZE);
+        assert!(leaf_used_spaces(&mut table).iter().all(|&used| used <= space / 2));
+        assert_tree_is_valid(&mut table);
+        compare_data(&mut table, rows);
+    }
+
+    #[test]
+    fn test_bulk_load_small_inputs() {
+        for count in [0, 1, rows_per_leaf(DEFAULT_PAGE_SIZE), rows_per_leaf(DEFAULT_PAGE_SIZE) + 1] {
+            let mut table = open_table();
+            let rows = test_rows(1..count as i32 + 1);
+            bulk_load::bulk_load(&mut table, &rows, DEFAULT_FILL_PERCENT).unwrap();
+            assert_tree_is_valid(&mut table);
+            compare_data(&mut table, rows);
+        }
+    }
+
+    #[test]
+    fn test_bulk_load_rejects_bad_input() {
+        let mut table = open_table();
+        let unsorted = [test_rows(1..5), test_rows(3..4)].concat();
+        let duplicate = [test_rows(1..5), test_rows(4..6)].concat();
+        for rows in [unsorted, duplicate] {
+            assert!(matches!(
+                bulk_load::bulk_load(&mut table, &rows, DEFAULT_FILL_PERCENT),
+                Err(DbError::InvalidArgument(_))
+            ));
+        }
+        assert!(bulk_load::bulk_load(&mut table, &test_rows(1..5), MIN_FILL_PERCENT - 1).is_err());
+        assert!(table.table_start().unwrap().end_of_table, "a rejected load should leave the table empty.");
+
+        insert_rows_in_range(&mut table, 1..2);
+        assert!(bulk_load::bulk_load(&mut table, &test_rows(2..5), DEFAULT_FILL_PERCENT).is_err());
+        compare_data(&mut table, test_rows(1..2));
+    }
+
+    #[test]
+    fn test_bulk_load_rolled_back_with_transaction() {
+        let mut table = open_table();
+        execute_transaction_statement(&mut table, StatementType::StatementBegin);
+        bulk_load::bulk_load(&mut table, &test_rows(1..1001), DEFAULT_FILL_PERCENT).unwrap();
+        execute_transaction_statement(&mut table, StatementType::StatementRollback);
+        assert!(table.table_start().unwrap().end_of_table, "the rolled back load should leave the table empty.");
+        assert_tree_is_valid(&mut table);
+    }
+
+    #[test]
+    fn test_read_rows() {
+        let file_path = format!("{}.rows", test_db_file_path());
+        std::fs::write(&file_path, "1 alice alice@test.com\n\n2 bob bob@test.com\n").unwrap();
+        let rows = bulk_load::read_rows(&file_path).unwrap();
+        assert_eq!(rows.iter().map(|row| row.id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(rows[1].username, "bob");
+
+        for contents in ["1 alice\n", "x alice alice@test.com\n", "-1 alice alice@test.com\n"] {
+            std::fs::write(&file_path, contents).unwrap();
+            assert!(matches!(bulk_load::read_rows(&file_path), Err(DbError::InvalidArgument(_))), "{}", contents);
+        }
+        std::fs::remove_file(&file_path).unwrap();
+    }
+
+    // Write-ahead log tests
+    #[test]
+    fn test_crc32() {
+        assert_eq!(checksum::crc32(b""), 0);
+        assert_eq!(checksum::crc32(b"123456789"), 0xCBF4_3926);
+        let bytes: Vec<u8> = (0..1000).map(|i| (i * 7) as u8).collect();
+        let (first, second) = bytes.split_at(333);
+        assert_eq!(checksum::crc32_update(checksum::crc32(first), second), checksum::crc32(&bytes));
+    }
+
+    #[test]
+    fn test_committed_rows_survive_crash() {
+        let db_file_path = test_db_file_path();
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        let inserted_rows = insert_shuffled_rows(&mut table, 300);
+        // dropping the table without closing it loses everything that is only in memory
+        drop(table);
+
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        compare_data(&mut table, inserted_rows);
+        assert_eq!(wal_file_length(&db_file_path), WAL_HEADER_SIZE, "recovery should checkpoint the log.");
+    }
+
+    #[test]
+    fn test_torn_commit_is_ignored() {
+        let db_file_path = test_db_file_path();
+        let mut table = Table::open(&db_file_path, &DbConfig::default()).unwrap();
+        let inserted_rows = insert_sequential_rows(&mut table, 10);
+        drop(table);
+        // cut the last commit record short as if the crash happened while writing it
+        let wal_file_path = format!("{}-wal", db_file_path);
+        let wal_file = std::fs::OpenOptions::new().write(true).open(&wal_file_path).unwrap();
+        wal_file.set_len((wal_file_length(&db_file_path) - 5) as u64).unwrap();
+
+        let mut table = Table::open(&db_file_path, &DbConfig::default()).unwrap();
+        compare_data(&mut table, inserted_rows[..9].to_vec());
+    }
+
+    #[test]
+    fn test_corrupt_frame_is_ignored() {
+        let db_file_path = test_db_file_path();
+        let mut table = Table::open(&db_file_path, &DbConfig::default()).unwrap();
+        let inserted_rows = insert_sequential_rows(&mut table, 5);
+        let wal_length = wal_file_length(&db_file_path);
+        let row = Row {
+            id: 6,
+            username: "user6".to_string(),
+            email: "user6@test.com".to_string(),
+        };
+        insert_row_internal(&mut table, &row);
+        drop(table);
+        // flip a byte inside the first frame of the last statement
+        let wal_file_path = format!("{}-wal", db_file_path);
+        let mut contents = std::fs::read(&wal_file_path).unwrap();
+        contents[wal_length + WAL_RECORD_HEADER_SIZE + 10] ^= 0xFF;
+        std::fs::write(&wal_file_path, contents).unwrap();
+
+        let mut table = Table::open(&db_file_path, &DbConfig::default()).unwrap();
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_uncommitted_frames_are_ignored() {
+        let db_file_path = test_db_file_path();
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            pool_size: 4,
+            ..DbConfig::default()
+        };
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        let inserted_rows = insert_sequential_rows(&mut table, 200);
+        // insert without committing, the small pool pushes dirty pages out to the log
+        insert_uncommitted_rows(&mut table, 201..500);
+        assert!(wal_file_length(&db_file_path) > WAL_HEADER_SIZE);
+        drop(table);
+
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_close_checkpoints_log() {
+        let db_file_path = test_db_file_path();
+        let mut table = Table::open(&db_file_path, &DbConfig::default()).unwrap();
+        let inserted_rows = insert_sequential_rows(&mut table, 100);
+        assert!(wal_file_length(&db_file_path) > WAL_HEADER_SIZE, "commits should go to the log.");
+        table.db_close();
+        assert_eq!(wal_file_length(&db_file_path), WAL_HEADER_SIZE, "close should empty the log.");
+
+        // the database file alone holds everything now
+        std::fs::remove_file(format!("{}-wal", db_file_path)).unwrap();
+        let mut table = Table::open(&db_file_path, &DbConfig::default()).unwrap();
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_log_is_checkpointed_automatically() {
+        let db_file_path = test_db_file_path();
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        let inserted_rows = insert_shuffled_rows(&mut table, 2000);
+        let max_wal_length = WAL_HEADER_SIZE
+            + (WAL_AUTOCHECKPOINT_FRAMES + table.pager.num_pages) * (WAL_RECORD_HEADER_SIZE + MIN_PAGE_SIZE);
+        assert!(wal_file_length(&db_file_path) < max_wal_length, "the log should not keep growing.");
+        drop(table);
+
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_open_rejects_bad_wal_magic() {
+        let db_file_path = test_db_file_path();
+        let mut table = Table::open(&db_file_path, &DbConfig::default()).unwrap();
+        table.db_close();
+        std::fs::write(format!("{}-wal", db_file_path), vec![0; WAL_HEADER_SIZE]).unwrap();
+        let error = open_error(&db_file_path);
+        assert!(error.contains("write-ahead log"), "unexpected error: {}", error);
+    }
+
+    // Journal mode tests
+    #[test]
+    fn test_rollback_journal_mode_persists_rows() {
+        let db_file_path = test_db_file_path();
+        let config = journal_config(JournalMode::Rollback);
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        let inserted_rows = insert_shuffled_rows(&mut table, 300);
+        assert!(!side_file_exists(&db_file_path, "-wal"), "journal mode should not keep a log.");
+        assert!(!side_file_exists(&db_file_path, "-journal"), "the journal should go away on commit.");
+        drop(table);
+
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_hot_journal_is_rolled_back() {
+        for reopen_mode in [JournalMode::Rollback, JournalMode::Wal] {
+            let db_file_path = test_db_file_path();
+            let config = DbConfig {
+                pool_size: 4,
+                ..journal_config(JournalMode::Rollback)
+            };
+            let mut table = Table::open(&db_file_path, &config).unwrap();
+            let inserted_rows = insert_sequential_rows(&mut table, 200);
+            let file_length = std::fs::metadata(&db_file_path).unwrap().len();
+            // insert without committing, the small pool writes pages to the file
+            insert_uncommitted_rows(&mut table, 201..500);
+            assert!(side_file_exists(&db_file_path, "-journal"), "overwriting pages should start a journal.");
+            assert!(std::fs::metadata(&db_file_path).unwrap().len() > file_length);
+            drop(table);
+
+            let mut table = Table::open(&db_file_path, &journal_config(reopen_mode)).unwrap();
+            assert!(!side_file_exists(&db_file_path, "-journal"), "the hot journal should be deleted.");
+            assert_eq!(std::fs::metadata(&db_file_path).unwrap().len(), file_length);
+            compare_data(&mut table, inserted_rows);
+        }
+    }
+
+    #[test]
+    fn test_journal_mode_off() {
+        let db_file_path = test_db_file_path();
+        let config = journal_config(JournalMode::Off);
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        let inserted_rows = insert_shuffled_rows(&mut table, 300);
+        table.db_close();
+        assert!(!side_file_exists(&db_file_path, "-wal") && !side_file_exists(&db_file_path, "-journal"));
+
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_switch_journal_mode() {
+        let db_file_path = test_db_file_path();
+        let mut table = Table::open(&db_file_path, &journal_config(JournalMode::Wal)).unwrap();
+        let mut inserted_rows = insert_shuffled_rows(&mut table, 100);
+        table.set_journal_mode(JournalMode::Rollback).unwrap();
+        assert!(!side_file_exists(&db_file_path, "-wal"), "leaving WAL mode should remove the log.");
+        inserted_rows.extend(insert_rows_in_range(&mut table, 101..200));
+        table.set_journal_mode(JournalMode::Off).unwrap();
+        inserted_rows.extend(insert_rows_in_range(&mut table, 200..300));
+        table.set_journal_mode(JournalMode::Wal).unwrap();
+        inserted_rows.extend(insert_rows_in_range(&mut table, 300..400));
+        assert_eq!(table.pager.journal_mode(), JournalMode::Wal);
+        drop(table);
+
+        // committed rows are recovered from the log even when opened in another mode
+        let mut table = Table::open(&db_file_path, &journal_config(JournalMode::Rollback)).unwrap();
+        assert!(!side_file_exists(&db_file_path, "-wal"), "the log should be replayed and removed.");
+        compare_data(&mut table, inserted_rows);
+    }
+
+    // Synchronous tests
+    #[test]
+    fn test_synchronous_names() {
+        assert_eq!(DbConfig::default().synchronous, Synchronous::Full, "the default should be durable.");
+        for synchronous in [Synchronous::Off, Synchronous::Normal, Synchronous::Full] {
+            assert_eq!(Synchronous::from_name(synchronous.name()), Some(synchronous));
+        }
+        assert_eq!(Synchronous::from_name("extra"), None);
+    }
+
+    #[test]
+    fn test_every_synchronous_level_persists_rows() {
+        for journal_mode in [JournalMode::Wal, JournalMode::Rollback, JournalMode::Off] {
+            for synchronous in [Synchronous::Off, Synchronous::Normal, Synchronous::Full] {
+                let db_file_path = test_db_file_path();
+                let config = DbConfig {
+                    pool_size: 4,
+                    synchronous,
+                    ..journal_config(journal_mode)
+                };
+                let mut table = Table::open(&db_file_path, &config).unwrap();
+                let mut inserted_rows = insert_sequential_rows(&mut table, 100);
+                // the process going away does not lose what reached the OS, synced or not
+                drop(table);
+
+                let mut table = Table::open(&db_file_path, &config).unwrap();
+                inserted_rows.extend(insert_rows_in_range(&mut table, 101..200));
+                table.db_close();
+                let mut table = Table::open(&db_file_path, &config).unwrap();
+                compare_data(&mut table, inserted_rows);
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_synchronous() {
+        let db_file_path = test_db_file_path();
+        let mut table = Table::open(&db_file_path, &journal_config(JournalMode::Rollback)).unwrap();
+        let mut inserted_rows = insert_sequential_rows(&mut table, 50);
+        table.set_synchronous(Synchronous::Off);
+        assert_eq!(table.pager.synchronous(), Synchronous::Off);
+        assert_eq!(table.config.synchronous, Synchronous::Off);
+        inserted_rows.extend(insert_rows_in_range(&mut table, 51..100));
+        table.set_synchronous(Synchronous::Normal);
+        inserted_rows.extend(insert_rows_in_range(&mut table, 100..150));
+        drop(table);
+
+        let mut table = Table::open(&db_file_path, &DbConfig::default()).unwrap();
+        compare_data(&mut table, inserted_rows);
+    }
+
+    // Transaction tests
+    #[test]
+    fn test_prepare_transaction_statements() {
+        assert!(matches!(
+            parser::prepare_statement("begin"),
+            PrepareResult::PrepareSuccess(Statement { statement_type: StatementType::StatementBegin, .. })
+        ));
+        assert!(matches!(
+            parser::prepare_statement("commit"),
+            PrepareResult::PrepareSuccess(Statement { statement_type: StatementType::StatementCommit, .. })
+        ));
+        assert!(matches!(
+            parser::prepare_statement("rollback"),
+            PrepareResult::PrepareSuccess(Statement { statement_type: StatementType::StatementRollback, .. })
+        ));
+        assert!(matches!(
+            parser::prepare_statement("begin now"),
+            PrepareResult::PrepareUnrecognizedStatement
+        ));
+    }
+
+    #[test]
+    fn test_rollback_discards_changes() {
+        for journal_mode in [JournalMode::Wal, JournalMode::Rollback, JournalMode::Off] {
+            let db_file_path = test_db_file_path();
+            let config = DbConfig {
+                pool_size: 4,
+                ..journal_config(journal_mode)
+            };
+            let mut table = Table::open(&db_file_path, &config).unwrap();
+            let inserted_rows = insert_sequential_rows(&mut table, 200);
+            let num_pages = table.pager.num_pages;
+
+            assert!(matches!(execute_transaction_statement(&mut table, StatementType::StatementBegin), ExecuteResult::ExecuteSuccess));
+            // the small pool pushes pages out while the transaction runs
+            insert_rows_in_range(&mut table, 201..500);
+            for id in 1..=100 {
+                assert!(matches!(delete_row(&mut table, id), ExecuteResult::ExecuteSuccess));
+            }
+            assert!(matches!(update_row(&mut table, 150, Some("changed"), None), ExecuteResult::ExecuteSuccess));
+            assert!(matches!(execute_transaction_statement(&mut table, StatementType::StatementRollback), ExecuteResult::ExecuteSuccess));
+
+            assert!(!table.pager.in_transaction());
+            assert_eq!(table.pager.num_pages, num_pages);
+            assert!(table.pager.free_list().unwrap().is_empty(), "pages freed by the transaction should be taken back.");
+            assert_tree_is_valid(&mut table);
+            compare_data(&mut table, inserted_rows.clone());
+            drop(table);
+
+            let mut table = Table::open(&db_file_path, &config).unwrap();
+            compare_data(&mut table, inserted_rows);
+        }
+    }
+
+    #[test]
+    fn test_commit_keeps_changes() {
+        let db_file_path = test_db_file_path();
+        let config = DbConfig {
+            pool_size: 4,
+            ..journal_config(JournalMode::Wal)
+        };
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        let mut inserted_rows = insert_sequential_rows(&mut table, 50);
+        execute_transaction_statement(&mut table, StatementType::StatementBegin);
+        inserted_rows.extend(insert_rows_in_range(&mut table, 51..300));
+        assert!(matches!(execute_transaction_statement(&mut table, StatementType::StatementCommit), ExecuteResult::ExecuteSuccess));
+        assert!(!table.pager.in_transaction());
+
+        // rows inserted after the commit are their own transactions again
+        inserted_rows.extend(insert_rows_in_range(&mut table, 300..310));
+        drop(table);
+
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_open_transaction_is_lost_on_crash() {
+        for journal_mode in [JournalMode::Wal, JournalMode::Rollback] {
+            let db_file_path = test_db_file_path();
+            let config = DbConfig {
+                pool_size: 4,
+                ..journal_config(journal_mode)
+            };
+            let mut table = Table::open(&db_file_path, &config).unwrap();
+            let inserted_rows = insert_sequential_rows(&mut table, 100);
+            execute_transaction_statement(&mut table, StatementType::StatementBegin);
+            insert_rows_in_range(&mut table, 101..400);
+            drop(table);
+
+            let mut table = Table::open(&db_file_path, &config).unwrap();
+            compare_data(&mut table, inserted_rows);
+        }
+    }
+
+    #[test]
+    fn test_transaction_statement_errors() {
+        let mut table = open_table();
+        assert!(matches!(execute_transaction_statement(&mut table, StatementType::StatementCommit), ExecuteResult::ExecuteNoTransaction));
+        assert!(matches!(execute_transaction_statement(&mut table, StatementType::StatementRollback), ExecuteResult::ExecuteNoTransaction));
+        execute_transaction_statement(&mut table, StatementType::StatementBegin);
+        assert!(matches!(execute_transaction_statement(&mut table, StatementType::StatementBegin), ExecuteResult::ExecuteTransactionOpen));
+        assert!(table.set_journal_mode(JournalMode::Off).is_err());
+        assert!(vacuum::vacuum(&mut table, 100).is_err());
+        assert!(table.pager.in_transaction(), "errors should leave the transaction open.");
+    }
+
+    #[test]
+    fn test_prepare_savepoint_statements() {
+        assert!(matches!(
+            parser::prepare_statement("savepoint batch1"),
+            PrepareResult::PrepareSuccess(Statement { statement_type: StatementType::StatementSavepoint(name), .. }) if name == "batch1"
+        ));
+        assert!(matches!(
+            parser::prepare_statement("release batch1"),
+            PrepareResult::PrepareSuccess(Statement { statement_type: StatementType::StatementRelease(name), .. }) if name == "batch1"
+        ));
+        assert!(matches!(
+            parser::prepare_statement("rollback to batch1"),
+            PrepareResult::PrepareSuccess(Statement { statement_type: StatementType::StatementRollbackTo(name), .. }) if name == "batch1"
+        ));
+        assert!(matches!(parser::prepare_statement("savepoint"), PrepareResult::PrepareUnrecognizedStatement));
+        assert!(matches!(parser::prepare_statement("rollback to"), PrepareResult::PrepareUnrecognizedStatement));
+    }
+
+    #[test]
+    fn test_rollback_to_nested_savepoints() {
+        for journal_mode in [JournalMode::Wal, JournalMode::Rollback, JournalMode::Off] {
+            let db_file_path = test_db_file_path();
+            let config = DbConfig {
+                pool_size: 4,
+                ..journal_config(journal_mode)
+            };
+            let mut table = Table::open(&db_file_path, &config).unwrap();
+            let mut inserted_rows = insert_sequential_rows(&mut table, 50);
+            execute_transaction_statement(&mut table, StatementType::StatementBegin);
+            inserted_rows.extend(insert_rows_in_range(&mut table, 51..100));
+            let first_rows = inserted_rows.clone();
+            let num_pages = table.pager.num_pages;
+
+            execute_transaction_statement(&mut table, StatementType::StatementSavepoint("first".to_string()));
+            // enough rows to split leaves and grow the tree
+            inserted_rows.extend(insert_rows_in_range(&mut table, 100..400));
+            let second_rows = inserted_rows.clone();
+            execute_transaction_statement(&mut table, StatementType::StatementSavepoint("second".to_string()));
+            for id in 1..=200 {
+                delete_row(&mut table, id);
+            }
+
+            let result = execute_transaction_statement(&mut table, StatementType::StatementRollbackTo("second".to_string()));
+            assert!(matches!(result, ExecuteResult::ExecuteSuccess));
+            compare_data(&mut table, second_rows);
+            let result = execute_transaction_statement(&mut table, StatementType::StatementRollbackTo("first".to_string()));
+            assert!(matches!(result, ExecuteResult::ExecuteSuccess));
+            assert_eq!(table.pager.num_pages, num_pages);
+            assert_tree_is_valid(&mut table);
+            compare_data(&mut table, first_rows.clone());
+
+            // the savepoint is still set and can be rolled back to again
+            insert_rows_in_range(&mut table, 100..200);
+            execute_transaction_statement(&mut table, StatementType::StatementRollbackTo("first".to_string()));
+            compare_data(&mut table, first_rows.clone());
+
+            let mut committed_rows = first_rows;
+            committed_rows.extend(insert_rows_in_range(&mut table, 100..150));
+            execute_transaction_statement(&mut table, StatementType::StatementCommit);
+            table.db_close();
+
+            let mut table = Table::open(&db_file_path, &config).unwrap();
+            compare_data(&mut table, committed_rows);
+        }
+    }
+
+    #[test]
+    fn test_release_keeps_changes_for_outer_rollback() {
+        let db_file_path = test_db_file_path();
+        let config = DbConfig {
+            pool_size: 4,
+            ..journal_config(JournalMode::Wal)
+        };
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        let inserted_rows = insert_sequential_rows(&mut table, 100);
+        execute_transaction_statement(&mut table, StatementType::StatementBegin);
+        execute_transaction_statement(&mut table, StatementType::StatementSavepoint("outer".to_string()));
+        execute_transaction_statement(&mut table, StatementType::StatementSavepoint("inner".to_string()));
+        // pages are first touched after the inner savepoint
+        insert_rows_in_range(&mut table, 101..300);
+        let result = execute_transaction_statement(&mut table, StatementType::StatementRelease("inner".to_string()));
+        assert!(matches!(result, ExecuteResult::ExecuteSuccess));
+
+        execute_transaction_statement(&mut table, StatementType::StatementRollbackTo("outer".to_string()));
+        compare_data(&mut table, inserted_rows.clone());
+        insert_rows_in_range(&mut table, 101..300);
+        execute_transaction_statement(&mut table, StatementType::StatementRelease("outer".to_string()));
+        execute_transaction_statement(&mut table, StatementType::StatementRollback);
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_savepoint_statement_errors() {
+        let mut table = open_table();
+        let savepoint = |name: &str| StatementType::StatementSavepoint(name.to_string());
+        let release = |name: &str| StatementType::StatementRelease(name.to_string());
+        let rollback_to = |name: &str| StatementType::StatementRollbackTo(name.to_string());
+        assert!(matches!(execute_transaction_statement(&mut table, savepoint("a")), ExecuteResult::ExecuteNoTransaction));
+        assert!(matches!(execute_transaction_statement(&mut table, rollback_to("a")), ExecuteResult::ExecuteNoTransaction));
+        execute_transaction_statement(&mut table, StatementType::StatementBegin);
+        assert!(matches!(execute_transaction_statement(&mut table, rollback_to("a")), ExecuteResult::ExecuteNoSuchSavepoint));
+        execute_transaction_statement(&mut table, savepoint("a"));
+        execute_transaction_statement(&mut table, savepoint("b"));
+        assert!(matches!(execute_transaction_statement(&mut table, release("a")), ExecuteResult::ExecuteSuccess));
+        // releasing a savepoint releases the newer ones too
+        assert!(matches!(execute_transaction_statement(&mut table, release("b")), ExecuteResult::ExecuteNoSuchSavepoint));
+        assert!(table.pager.in_transaction(), "releasing savepoints should leave the transaction open.");
+    }
+
+    // File header tests
+    #[test]
+    fn test_header_written_for_new_file() {
+        let mut table = open_table();
+        let header = FileHeader::read(table.pager.get_page_ref(HEADER_PAGE_NUM).unwrap()).unwrap();
+        assert_eq!(header.version, HEADER_FORMAT_VERSION);
+        assert_eq!(header.page_size as usize, DEFAULT_PAGE_SIZE);
+        assert_eq!(header.root_page_num, 1);
+        assert_eq!(header.free_list_head, 0);
+    }
+
+    #[test]
+    fn test_reopen_reads_header() {
+        let db_file_path = test_db_file_path();
+        let mut table = Table::open(&db_file_path, &DbConfig::default()).unwrap();
+        let inserted_rows = insert_sequential_rows(&mut table, 10);
+        let num_pages = table.pager.num_pages;
+        table.db_close();
+
+        let mut table = Table::open(&db_file_path, &DbConfig::default()).unwrap();
+        let header = FileHeader::read(table.pager.get_page_ref(HEADER_PAGE_NUM).unwrap()).unwrap();
+        assert_eq!(header.page_count as usize, num_pages);
+        assert_eq!(table.root_page_num, header.root_page_num as usize);
+        compare_data(&mut table, inserted_rows);
+    }
+
+    #[test]
+    fn test_open_rejects_bad_magic() {
+        let db_file_path = test_db_file_path();
+        std::fs::write(&db_file_path, vec![b'x'; DEFAULT_PAGE_SIZE * 2]).unwrap();
+        let error = open_error(&db_file_path);
+        assert!(error.contains("bad magic number"), "unexpected error: {}", error);
+    }
+
+    #[test]
+    fn test_open_rejects_other_version() {
+        let db_file_path = test_db_file_path();
+        write_header_field(&db_file_path, HEADER_VERSION_OFFSET, HEADER_FORMAT_VERSION + 1);
+        let error = open_error(&db_file_path);
+        assert!(error.contains("unsupported format version"), "unexpected error: {}", error);
+    }
+
+    #[test]
+    fn test_open_rejects_invalid_page_size() {
+        let db_file_path = test_db_file_path();
+        write_header_field(&db_file_path, HEADER_PAGE_SIZE_OFFSET, 1000);
+        let error = open_error(&db_file_path);
+        assert!(error.contains("page size 1000"), "unexpected error: {}", error);
+    }
+
+    #[test]
+    fn test_open_rejects_truncated_file() {
+        let db_file_path = test_db_file_path();
+        let mut table = Table::open(&db_file_path, &DbConfig::default()).unwrap();
+        insert_sequential_rows(&mut table, leaf_node_max_cells(DEFAULT_PAGE_SIZE) * 3);
+        table.db_close();
+        let file = std::fs::OpenOptions::new().write(true).open(&db_file_path).unwrap();
+        file.set_len((DEFAULT_PAGE_SIZE * 3) as u64).unwrap();
+
+        let error = open_error(&db_file_path);
+        assert!(error.contains("truncated"), "unexpected error: {}", error);
+    }
+
+    #[test]
+    fn test_open_rejects_bad_free_list_head() {
+        let db_file_path = test_db_file_path();
+        write_header_field(&db_file_path, HEADER_FREE_LIST_HEAD_OFFSET, 2);
+        let error = open_error(&db_file_path);
+        assert!(error.contains("free list head"), "unexpected error: {}", error);
+    }
+
+    #[test]
+    fn test_open_rejects_partial_page() {
+        let db_file_path = test_db_file_path();
+        let mut table = Table::open(&db_file_path, &DbConfig::default()).unwrap();
+        table.db_close();
+        let mut contents = std::fs::read(&db_file_path).unwrap();
+        contents.push(0);
+        std::fs::write(&db_file_path, contents).unwrap();
+        let error = open_error(&db_file_path);
+        assert!(error.contains("whole number of pages"), "unexpected error: {}", error);
+    }
+
+    // Page checksum tests
+    #[test]
+    fn test_corrupt_page_is_reported() {
+        let db_file_path = test_db_file_path();
+        let mut table = Table::open(&db_file_path, &DbConfig::default()).unwrap();
+        insert_sequential_rows(&mut table, leaf_node_max_cells(DEFAULT_PAGE_SIZE) * 3);
+        table.db_close();
+        let mut contents = std::fs::read(&db_file_path).unwrap();
+        contents[2 * DEFAULT_PAGE_SIZE + 100] ^= 0x01;
+        std::fs::write(&db_file_path, contents).unwrap();
+
+        let mut table = Table::open(&db_file_path, &DbConfig::default()).unwrap();
+        let error = table.pager.get_page_ref(2).unwrap_err();
+        assert!(matches!(error, DbError::CorruptPage(2)), "unexpected error: {}", error);
+        assert!(error.to_string().contains("Page 2"), "unexpected error: {}", error);
+        assert!(table.pager.get_page_ref(3).is_ok(), "other pages should still be readable.");
+    }
+
+    #[test]
+    fn test_misplaced_page_is_reported() {
+        let db_file_path = test_db_file_path();
+        let mut table = Table::open(&db_file_path, &DbConfig::default()).unwrap();
+        insert_sequential_rows(&mut table, leaf_node_max_cells(DEFAULT_PAGE_SIZE) * 3);
+        table.db_close();
+        // a page with a valid checksum written where another page belongs
+        let mut contents = std::fs::read(&db_file_path).unwrap();
+        contents.copy_within(2 * DEFAULT_PAGE_SIZE..3 * DEFAULT_PAGE_SIZE, 3 * DEFAULT_PAGE_SIZE);
+        std::fs::write(&db_file_path, contents).unwrap();
+
+        let mut table = Table::open(&db_file_path, &DbConfig::default()).unwrap();
+        assert!(matches!(table.pager.get_page_ref(3), Err(DbError::CorruptPage(3))));
+    }
+
+    #[test]
+    fn test_open_rejects_corrupt_header_page() {
+        let db_file_path = test_db_file_path();
+        let mut table = Table::open(&db_file_path, &DbConfig::default()).unwrap();
+        table.db_close();
+        // past the header fields, so only the checksum can notice
+        let mut contents = std::fs::read(&db_file_path).unwrap();
+        contents[HEADER_SIZE + 10] ^= 0xFF;
+        std::fs::write(&db_file_path, contents).unwrap();
+        let error = open_error(&db_file_path);
+        assert!(error.contains("Page 0 is corrupt"), "unexpected error: {}", error);
+    }
+
+    #[test]
+    fn test_reading_corrupt_page_returns_error() {
+        let db_file_path = test_db_file_path();
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        insert_sequential_rows(&mut table, 100);
+        let last_leaf = table.table_find(100).unwrap().page_num;
+        assert_ne!(last_leaf, table.table_start().unwrap().page_num, "rows should span several leaves.");
+        table.db_close();
+        let mut contents = std::fs::read(&db_file_path).unwrap();
+        contents[last_leaf * MIN_PAGE_SIZE + 50] ^= 0x01;
+        std::fs::write(&db_file_path, contents).unwrap();
+
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        assert!(matches!(table.table_find(100), Err(DbError::CorruptPage(page_num)) if page_num == last_leaf));
+        let select_all = Statement {
+            row_to_insert: None,
+            statement_type: StatementType::StatementSelect(KeyRange::all(), ScanOrder::Ascending),
+        };
+        match executor::execute_statement(&select_all, &mut table) {
+            ExecuteResult::ExecuteError(DbError::CorruptPage(page_num)) => assert_eq!(page_num, last_leaf),
+            _ => panic!("the scan should stop at the corrupt leaf."),
+        }
+        // rows on other pages can still be read
+        let select_first = Statement {
+            row_to_insert: None,
+            statement_type: StatementType::StatementSelect(
+                KeyRange {
+                    start: Bound::Included(1),
+                    end: Bound::Included(1),
+                },
+                ScanOrder::Ascending,
+            ),
+        };
+        assert!(matches!(executor::execute_statement(&select_first, &mut table), ExecuteResult::ExecuteSuccess));
+    }
+
+    #[test]
+    fn test_delete_next_to_corrupt_page_returns_error() {
+        let db_file_path = test_db_file_path();
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        insert_sequential_rows(&mut table, 100);
+        let first_leaf = table.table_start().unwrap().page_num;
+        let second_leaf = node::leaf_node_next_leaf(table.pager.get_page_ref(first_leaf).unwrap()) as usize;
+        table.db_close();
+        let mut contents = std::fs::read(&db_file_path).unwrap();
+        contents[second_leaf * MIN_PAGE_SIZE + 50] ^= 0x01;
+        std::fs::write(&db_file_path, contents).unwrap();
+
+        // deletes from the first leaf until it underflows and is merged with the corrupt one next to it
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        let mut id = 1;
+        loop {
+            let delete = Statement {
+                row_to_insert: None,
+                statement_type: StatementType::StatementDelete(id),
+            };
+            match executor::execute_statement(&delete, &mut table) {
+                ExecuteResult::ExecuteSuccess => id += 1,
+                ExecuteResult::ExecuteError(DbError::CorruptPage(page_num)) => {
+                    assert_eq!(page_num, second_leaf);
+                    break;
+                }
+                _ => panic!("the delete should stop at the corrupt leaf."),
+            }
+            assert_eq!(table.table_start().unwrap().page_num, first_leaf, "the first leaf should underflow first.");
+        }
+        // the half done delete was undone, not committed
+        assert_eq!(table.table_find(id).unwrap().cursor_key().unwrap(), Some(id));
+        table.db_close();
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        assert_eq!(table.table_find(id).unwrap().cursor_key().unwrap(), Some(id));
+    }
+
+    // Integrity check tests
+    #[test]
+    fn test_check_passes_after_inserts_and_deletes() {
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = open_table_with_config(&config);
+        assert_tree_is_valid(&mut table);
+        insert_shuffled_rows(&mut table, 2000);
+        assert_tree_is_valid(&mut table);
+        for id in (1..=2000).filter(|id| id % 3 != 0) {
+            delete_row(&mut table, id);
+        }
+        assert!(!table.pager.free_list().unwrap().is_empty(), "deletes should have freed pages.");
+        assert_tree_is_valid(&mut table);
+    }
+
+    #[test]
+    fn test_check_reports_keys_out_of_order() {
+        let mut table = open_table();
+        insert_sequential_rows(&mut table, 10);
+        let root = table.pager.get_page(table.root_page_num).unwrap();
+        leaf_node_cell_mut(root, 3)[LEAF_NODE_KEY_OFFSET..LEAF_NODE_KEY_OFFSET + LEAF_NODE_KEY_SIZE]
+            .copy_from_slice(&100u32.to_le_bytes());
+        assert_violations(&mut table, &["page 1: keys 100 and 5 are out of order"]);
+    }
+
+    #[test]
+    fn test_check_reports_bad_cell_layout() {
+        let mut table = open_table();
+        insert_sequential_rows(&mut table, 10);
+        let root = table.pager.get_page(table.root_page_num).unwrap();
+        let cells_size = node::leaf_node_used_space(root) - 10 * LEAF_NODE_SLOT_SIZE;
+        let fragmented_offset = LEAF_NODE_FRAGMENTED_BYTES_OFFSET;
+        root[fragmented_offset..fragmented_offset + LEAF_NODE_FRAGMENTED_BYTES_SIZE]
+            .copy_from_slice(&5u16.to_le_bytes());
+        assert_violations(
+            &mut table,
+            &[&format!(
+                "page 1: cells take {} bytes and 5 are fragmented but the cell content area is {} bytes",
+                cells_size, cells_size
+            )],
+        );
+
+        let root = table.pager.get_page(table.root_page_num).unwrap();
+        let slot_offset = LEAF_NODE_HEADER_SIZE + 3 * LEAF_NODE_SLOT_SIZE;
+        root[slot_offset..slot_offset + LEAF_NODE_SLOT_SIZE].copy_from_slice(&4090u16.to_le_bytes());
+        assert_violations(&mut table, &["page 1: cell 3 at offset 4090 is outside the cell content area"]);
+    }
+
+    #[test]
+    fn test_check_reports_bad_links() {
+        let mut table = open_table();
+        insert_sequential_rows(&mut table, leaf_node_max_cells(DEFAULT_PAGE_SIZE) * 3);
+        let root = table.pager.get_page_ref(table.root_page_num).unwrap().to_vec();
+        let first_leaf = internal_node_child_page_num(&root, 0);
+        let second_leaf = internal_node_child_page_num(&root, 1);
+        // a wrong parent pointer and a leaf chain that skips the second leaf
+        let third_leaf = leaf_node_next_leaf(table.pager.get_page_ref(second_leaf).unwrap());
+        node::set_node_parent(table.pager.get_page(second_leaf).unwrap(), 42);
+        node::set_next_leaf(table.pager.get_page(first_leaf).unwrap(), third_leaf);
+        assert_violations(
+            &mut table,
+            &[
+                &format!("page {}: parent pointer is 42 but the node is a child of 1", second_leaf),
+                &format!("page {}: next leaf is {} but the next leaf in key order is {}", first_leaf, third_leaf, second_leaf),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_check_reports_bad_prev_links() {
+        let mut table = open_table();
+        insert_sequential_rows(&mut table, leaf_node_max_cells(DEFAULT_PAGE_SIZE) * 3);
+        let root = table.pager.get_page_ref(table.root_page_num).unwrap().to_vec();
+        let first_leaf = internal_node_child_page_num(&root, 0);
+        let second_leaf = internal_node_child_page_num(&root, 1);
+        node::set_prev_leaf(table.pager.get_page(first_leaf).unwrap(), second_leaf as u32);
+        node::set_prev_leaf(table.pager.get_page(second_leaf).unwrap(), 0);
+        assert_violations(
+            &mut table,
+            &[
+                &format!("page {}: first leaf links back to page {} instead of starting the chain", first_leaf, second_leaf),
+                &format!("page {}: previous leaf is 0 but the previous leaf in key order is {}", second_leaf, first_leaf),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_check_reports_shared_and_lost_pages() {
+        let mut table = open_table();
+        insert_sequential_rows(&mut table, leaf_node_max_cells(DEFAULT_PAGE_SIZE) * 3);
+        let mut root = table.pager.get_page_ref(table.root_page_num).unwrap().to_vec();
+        let first_leaf = internal_node_child_page_num(&root, 0);
+        let second_leaf = internal_node_child_page_num(&root, 1);
+        // the root's second child points at the first leaf again
+        internal_node_child(&mut root, 1).copy_from_slice(&first_leaf.to_le_bytes());
+        table.pager.get_page(table.root_page_num).unwrap().copy_from_slice(&root);
+
+        let violations: Vec<String> = check::check_table(&mut table).iter().map(|v| v.to_string()).collect();
+        let shared = format!("page {}: page 1 points at a page that is already in use", first_leaf);
+        let lost = format!("page {}: page is neither in the tree nor on the free list", second_leaf);
+        assert!(violations.contains(&shared), "missing '{}' in {:?}", shared, violations);
+        assert!(violations.contains(&lost), "missing '{}' in {:?}", lost, violations);
+    }
+
+    #[test]
+    fn test_check_reports_stale_internal_key() {
+        let mut table = open_table();
+        insert_sequential_rows(&mut table, leaf_node_max_cells(DEFAULT_PAGE_SIZE) * 3);
+        let root = table.pager.get_page_ref(table.root_page_num).unwrap().to_vec();
+        let first_leaf = internal_node_child_page_num(&root, 0);
+        let second_leaf = internal_node_child_page_num(&root, 1);
+        let max_key = u32::from_le_bytes(internal_node_key(&root, 0).try_into().unwrap());
+        // the raised key also overlaps the first key of the next leaf
+        node::update_internal_node_key(table.pager.get_page(table.root_page_num).unwrap(), max_key + 1, 0);
+        assert_violations(
+            &mut table,
+            &[
+                &format!("page 1: key {} does not match the max key {} of child {}", max_key + 1, max_key, first_leaf),
+                &format!("page {}: key {} is not above {} from the parent", second_leaf, max_key + 1, max_key + 1),
+            ],
+        );
+    }
+
+    // Range scan tests
+    #[test]
+    fn test_prepare_select_ranges() {
+        let cases = [
+            ("select", Bound::Unbounded, Bound::Unbounded),
+            ("select where id between 2 and 5", Bound::Included(2), Bound::Included(5)),
+            ("select where id = 7", Bound::Included(7), Bound::Included(7)),
+            ("select where id < 7", Bound::Unbounded, Bound::Excluded(7)),
+            ("select where id <= 7", Bound::Unbounded, Bound::Included(7)),
+            ("select where id > 7", Bound::Excluded(7), Bound::Unbounded),
+            ("select where id >= 7", Bound::Included(7), Bound::Unbounded),
+        ];
+        for (input, start, end) in cases {
+            match parser::prepare_statement(input) {
+                PrepareResult::PrepareSuccess(Statement {
+                    statement_type: StatementType::StatementSelect(range, ScanOrder::Ascending),
+                    ..
+                }) => assert_eq!((range.start, range.end), (start, end), "'{}'", input),
+                _ => panic!("'{}' should parse.", input),
+            }
+        }
+        assert!(matches!(parser::prepare_statement("select where id >= -1"), PrepareResult::PrepareNegativeId));
+        for input in ["select *", "select where id", "select where id != 3", "select where id between 1 5"] {
+            assert!(matches!(parser::prepare_statement(input), PrepareResult::PrepareSyntaxError), "'{}'", input);
+        }
+    }
+
+    #[test]
+    fn test_prepare_select_order() {
+        let cases = [
+            ("select order by id asc", ScanOrder::Ascending),
+            ("select order by id desc", ScanOrder::Descending),
+            ("select where id > 7 order by id desc", ScanOrder::Descending),
+        ];
+        for (input, expected) in cases {
+            match parser::prepare_statement(input) {
+                PrepareResult::PrepareSuccess(Statement {
+                    statement_type: StatementType::StatementSelect(_, order),
+                    ..
+                }) => assert_eq!(order, expected, "'{}'", input),
+                _ => panic!("'{}' should parse.", input),
+            }
+        }
+        for input in ["select order by id", "select order by name desc", "select order by id desc where id > 7"] {
+            assert!(matches!(parser::prepare_statement(input), PrepareResult::PrepareSyntaxError), "'{}'", input);
+        }
+    }
+
+    #[test]
+    fn test_seek() {
+        let mut table = open_table();
+        for id in (2..=400).step_by(2) {
+            let row = Row {
+                id,
+                username: format!("user{}", id),
+                email: format!("user{}@test.com", id),
+            };
+            insert_row_internal(&mut table, &row);
+        }
+        assert!(tree_depth(&mut table) > 1, "the rows should span several leaves.");
+        for key in [0, 1, 2, 143, 144, 399] {
+            let expected = if key % 2 == 0 { key.max(2) } else { key + 1 };
+            assert_eq!(table.seek_ge(key).unwrap().cursor_key().unwrap(), Some(expected), "seek_ge({})", key);
+            assert_eq!(table.seek_gt(key).unwrap().cursor_key().unwrap(), Some(key / 2 * 2 + 2), "seek_gt({})", key);
+        }
+        // every leaf but the last ends below the next leaf's keys, seeking between them moves on
+        let root = table.pager.get_page_ref(table.root_page_num).unwrap().to_vec();
+        let first_leaf_max = u32::from_le_bytes(internal_node_key(&root, 0).try_into().unwrap());
+        let cursor = table.seek_gt(first_leaf_max).unwrap();
+        assert_eq!(cursor.page_num, internal_node_child_page_num(&root, 1));
+        assert_eq!(cursor.cell_num, 0);
+        drop(cursor);
+        assert!(table.seek_ge(401).unwrap().end_of_table);
+        assert!(table.seek_gt(400).unwrap().end_of_table);
+    }
+
+    #[test]
+    fn test_seek_backwards() {
+        let mut table = open_table();
+        assert!(table.table_end().unwrap().end_of_table, "an empty table has no last row.");
+        for id in (2..=400).step_by(2) {
+            let row = Row {
+                id,
+                username: format!("user{}", id),
+                email: format!("user{}@test.com", id),
+            };
+            insert_row_internal(&mut table, &row);
+        }
+        assert_eq!(table.table_end().unwrap().cursor_key().unwrap(), Some(400));
+        for key in [3, 143, 144, 400, 401] {
+            assert_eq!(table.seek_le(key).unwrap().cursor_key().unwrap(), Some(key.min(400) / 2 * 2), "seek_le({})", key);
+            assert_eq!(table.seek_lt(key).unwrap().cursor_key().unwrap(), Some((key - 1) / 2 * 2), "seek_lt({})", key);
+        }
+        // the first key of a leaf steps back into the previous leaf
+        let root = table.pager.get_page_ref(table.root_page_num).unwrap().to_vec();
+        let first_leaf_max = u32::from_le_bytes(internal_node_key(&root, 0).try_into().unwrap());
+        let cursor = table.seek_lt(first_leaf_max + 2).unwrap();
+        assert_eq!(cursor.page_num, internal_node_child_page_num(&root, 0));
+        drop(cursor);
+        assert!(table.seek_le(1).unwrap().end_of_table);
+        assert!(table.seek_lt(2).unwrap().end_of_table);
+        let mut cursor = table.table_start().unwrap();
+        cursor.retreat().unwrap();
+        assert!(cursor.end_of_table, "retreating from the first row ends the scan.");
+        assert_eq!(cursor.cursor_key().unwrap(), None);
+    }
+
+    #[test]
+    fn test_scan_range() {
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = open_table_with_config(&config);
+        insert_shuffled_rows(&mut table, 500);
+        assert!(tree_depth(&mut table) > 2, "the rows should span several levels.");
+        let ranges = [
+            (Bound::Unbounded, Bound::Unbounded),
+            (Bound::Included(100), Bound::Included(250)),
+            (Bound::Excluded(100), Bound::Excluded(250)),
+            (Bound::Included(499), Bound::Unbounded),
+            (Bound::Unbounded, Bound::Included(1)),
+            (Bound::Included(42), Bound::Included(42)),
+            (Bound::Included(600), Bound::Unbounded),
+            (Bound::Included(300), Bound::Included(200)),
+        ];
+        for (start, end) in ranges {
+            let range = KeyRange { start, end };
+            let mut expected: Vec<u32> = (1..=500).filter(|id| (start, end).contains(id)).collect();
+            assert_eq!(scan_ids(&mut table, &range, ScanOrder::Ascending), expected, "{:?}..{:?}", start, end);
+            expected.reverse();
+            assert_eq!(scan_ids(&mut table, &range, ScanOrder::Descending), expected, "{:?}..{:?} desc", start, end);
+        }
+    }
+
+    #[test]
+    fn test_descending_scan_after_deletes() {
+        let config = DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            ..DbConfig::default()
+        };
+        let mut table = open_table_with_config(&config);
+        insert_shuffled_rows(&mut table, 1000);
+        let mut ids: Vec<u32> = (1..=1000).collect();
+        ids.shuffle(&mut rand::thread_rng());
+        let (deleted, kept) = ids.split_at(700);
+        for &id in deleted {
+            delete_row(&mut table, id);
+        }
+        assert_tree_is_valid(&mut table);
+        let mut expected = kept.to_vec();
+        expected.sort_unstable_by(|a, b| b.cmp(a));
+        assert_eq!(scan_ids(&mut table, &KeyRange::all(), ScanOrder::Descending), expected);
+    }
+
+    // Tree printing tests
+    #[test]
+    fn test_format_tree_single_leaf() {
+        let mut table = open_table();
+        insert_sequential_rows(&mut table, 3);
+        assert_eq!(node::format_tree(&mut table).unwrap(), "- leaf (page 1, size 3)\n  - 1\n  - 2\n  - 3\n");
+    }
+
+    #[test]
+    fn test_format_tree_after_split() {
+        let mut table = open_table();
+        let num_rows = rows_per_leaf(DEFAULT_PAGE_SIZE) + 1;
+        insert_sequential_rows(&mut table, num_rows);
+        let root = table.pager.get_page_ref(table.root_page_num).unwrap().to_vec();
+        let left_leaf = internal_node_child_page_num(&root, 0);
+        let left_count = leaf_node_num_cells(table.pager.get_page_ref(left_leaf).unwrap()) as usize;
+        let mut expected = "- internal (page 1, size 1)\n".to_string();
+        expected += &format!("  - leaf (page {}, size {})\n", left_leaf, left_count);
+        for key in 1..=left_count {
+            expected += &format!("    - {}\n", key);
+        }
+        expected += &format!("  - key {}\n", left_count);
+        expected += &format!(
+            "  - leaf (page {}, size {})\n",
+            internal_node_child_page_num(&root, 1),
+            num_rows - left_count
+        );
+        for key in left_count + 1..=num_rows {
+            expected += &format!("    - {}\n", key);
+        }
+        assert_eq!(node::format_tree(&mut table).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_dot() {
+        let mut table = open_table();
+        insert_sequential_rows(&mut table, rows_per_leaf(DEFAULT_PAGE_SIZE) + 1);
+        let root = table.pager.get_page_ref(table.root_page_num).unwrap().to_vec();
+        let left_leaf = internal_node_child_page_num(&root, 0);
+        let right_leaf = internal_node_child_page_num(&root, 1);
+        let left_count = leaf_node_num_cells(table.pager.get_page_ref(left_leaf).unwrap());
+        let dot = node::format_dot(&mut table).unwrap();
+        assert!(dot.starts_with("digraph btree {\n"), "{}", dot);
+        assert!(dot.ends_with("}\n"), "{}", dot);
+        assert!(dot.contains(&format!("page1 [label=\"page 1 (internal)\\n{}\"];", left_count)), "{}", dot);
+        assert!(dot.contains(&format!("page1 -> page{};", left_leaf)), "{}", dot);
+        assert!(dot.contains(&format!("page1 -> page{};", right_leaf)), "{}", dot);
+        assert!(dot.contains(&format!("page{} [label=\"page {} (leaf)\\n1, 2, 3,", left_leaf, left_leaf)), "{}", dot);
+        assert!(dot.contains(&format!("page{} -> page{} [style=dotted];", left_leaf, right_leaf)), "{}", dot);
+        assert_eq!(dot.matches("style=dotted").count(), 1, "only the first leaf has a next leaf.");
+    }
+
+    // Inspector tests
+    #[test]
+    fn test_inspect_page_type_histogram() {
+        let mut table = open_table();
+        insert_sequential_rows(&mut table, leaf_node_max_cells(DEFAULT_PAGE_SIZE) * 3);
+        for id in 1..=leaf_node_max_cells(DEFAULT_PAGE_SIZE) as u32 {
+            delete_row(&mut table, id);
+        }
+        let free_pages = table.pager.free_list().unwrap().len();
+        assert!(free_pages > 0, "deletes should have freed pages.");
+        let db_file_path = table.file_name.clone();
+        table.db_close();
+
+        let mut page_file = inspect::PageFile::open(&db_file_path, None).unwrap();
+        let histogram = inspect::page_type_histogram(&mut page_file).unwrap();
+        let leaves = page_file.num_pages - 2 - free_pages;
+        assert_eq!(
+            histogram,
+            format!(
+                "header: 1\ninternal: 1\nleaf: {}\nfree: {}\noverflow: 0\nunknown: 0\nchecksum mismatches: 0\n",
+                leaves, free_pages
+            )
+        );
+    }
+
+    #[test]
+    fn test_inspect_decodes_pages() {
+        let mut table = open_table();
+        insert_sequential_rows(&mut table, 2);
+        let db_file_path = table.file_name.clone();
+        table.db_close();
+
+        let mut page_file = inspect::PageFile::open(&db_file_path, None).unwrap();
+        let header = inspect::describe_page(0, &page_file.read_page(0).unwrap());
+        assert!(header.contains("page_size: 4096\n"), "{}", header);
+        let root = page_file.read_page(1).unwrap();
+        assert_eq!(
+            inspect::describe_page(1, &root),
+            "page 1: leaf\n  checksum: ok\n  is_root: true\n  parent: 0\n  num_cells: 2\n  next_leaf: 0\n  prev_leaf: 0\n  \
+             cell_content_start: 4030\n  fragmented_bytes: 0\n"
+        );
+        assert_eq!(
+            inspect::describe_cells(1, &root),
+            "  cell 0: key 1 (1, user1, user1@test.com)\n  cell 1: key 2 (2, user2, user2@test.com)\n"
+        );
+        let hexdump = inspect::hexdump(&root);
+        assert_eq!(hexdump.lines().count(), DEFAULT_PAGE_SIZE / 16);
+        assert!(hexdump.starts_with("0000  01 01 00 00 00 00 02 00 00 00"), "{}", hexdump);
+        assert!(matches!(page_file.read_page(2), Err(DbError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_inspect_opens_file_with_damaged_header() {
+        let db_file_path = test_db_file_path();
+        let config = DbConfig {
+            page_size: 1024,
+            ..DbConfig::default()
+        };
+        let mut table = Table::open(&db_file_path, &config).unwrap();
+        insert_sequential_rows(&mut table, 2);
+        table.db_close();
+        let mut contents = std::fs::read(&db_file_path).unwrap();
+        contents[HEADER_PAGE_SIZE_OFFSET..HEADER_PAGE_SIZE_OFFSET + HEADER_PAGE_SIZE_SIZE]
+            .copy_from_slice(&3u32.to_le_bytes());
+        std::fs::write(&db_file_path, &contents).unwrap();
+
+        // the page size is guessed from the pages after the header
+        let mut page_file = inspect::PageFile::open(&db_file_path, None).unwrap();
+        assert_eq!(page_file.page_size, 1024);
+        assert!(matches!(page_file.header_error, Some(DbError::InvalidFile(_))));
+        let root = page_file.read_page(1).unwrap();
+        assert!(inspect::describe_page(1, &root).contains("num_cells: 2\n"));
+
+        let page_file = inspect::PageFile::open(&db_file_path, Some(1024)).unwrap();
+        assert!(page_file.header_error.is_some());
+        assert!(matches!(inspect::PageFile::open(&db_file_path, Some(1000)), Err(DbError::InvalidFile(_))));
+
+        // with the root damaged as well no page size can be trusted
+        contents[1024 + 20] ^= 0xFF;
+        std::fs::write(&db_file_path, &contents).unwrap();
+        assert!(matches!(inspect::PageFile::open(&db_file_path, None), Err(DbError::InvalidFile(_))));
+        assert!(inspect::PageFile::open(&db_file_path, Some(1024)).is_ok());
+    }
+
+    #[test]
+    fn test_inspect_reports_corrupt_cells() {
+        let mut table = open_table();
+        insert_sequential_rows(&mut table, 4);
+        let db_file_path = table.file_name.clone();
+        table.db_close();
+
+        let mut root = inspect::PageFile::open(&db_file_path, None).unwrap().read_page(1).unwrap();
+        let username_offset =
+            |root: &[u8], cell_num| node::leaf_node_cell_offset(root, cell_num) + LEAF_NODE_PAYLOAD_OFFSET + ID_SIZE;
+        // a username length running past the cell
+        let offset = username_offset(&root, 0);
+        root[offset..offset + FIELD_LENGTH_SIZE].copy_from_slice(&u16::MAX.to_le_bytes());
+        // a username that is not UTF-8
+        let offset = username_offset(&root, 1);
+        root[offset + FIELD_LENGTH_SIZE] = 0xFF;
+        // a slot pointing into the header
+        root[LEAF_NODE_HEADER_SIZE + 2 * LEAF_NODE_SLOT_SIZE..LEAF_NODE_HEADER_SIZE + 3 * LEAF_NODE_SLOT_SIZE]
+            .copy_from_slice(&5u16.to_le_bytes());
+        assert_eq!(
+            inspect::describe_cells(1, &root),
+            "  cell 0: key 1 corrupt cell, row cannot be decoded\n  cell 1: key 2 corrupt cell, row cannot be decoded\n  \
+             cell 2: corrupt cell at offset 5\n  cell 3: key 4 (4, user4, user4@test.com)\n"
+        );
+    }
+
+    #[test]
+    fn test_undecodable_row_returns_error() {
+        let mut table = open_table();
+        insert_sequential_rows(&mut table, 4);
+        // a username length running past the cell, written with a valid checksum
+        let root = table.pager.get_page(table.root_page_num).unwrap();
+        let offset = node::leaf_node_cell_offset(root, 1) + LEAF_NODE_PAYLOAD_OFFSET + ID_SIZE;
+        root[offset..offset + FIELD_LENGTH_SIZE].copy_from_slice(&u16::MAX.to_le_bytes());
+        let root_page_num = table.root_page_num;
+
+        for order in [ScanOrder::Ascending, ScanOrder::Descending] {
+            let select_all = Statement {
+                row_to_insert: None,
+                statement_type: StatementType::StatementSelect(KeyRange::all(), order),
+            };
+            match executor::execute_statement(&select_all, &mut table) {
+                ExecuteResult::ExecuteError(DbError::CorruptPage(page_num)) => assert_eq!(page_num, root_page_num),
+                _ => panic!("the scan should stop at the row it cannot decode."),
+            }
+        }
+        let update = Statement {
+            row_to_insert: None,
+            statement_type: StatementType::StatementUpdate(
+                2,
+                RowUpdate {
+                    username: Some("renamed".to_string()),
+                    email: None,
+                },
+            ),
+        };
+        match executor::execute_statement(&update, &mut table) {
+            ExecuteResult::ExecuteError(DbError::CorruptPage(page_num)) => assert_eq!(page_num, root_page_num),
+            _ => panic!("the update should stop at the row it cannot decode."),
+        }
+    }
+
+    #[test]
+    fn test_inspect_reports_checksum_mismatch() {
+        let mut table = open_table();
+        insert_sequential_rows(&mut table, 2);
+        let db_file_path = table.file_name.clone();
+        table.db_close();
+        write_header_field(&db_file_path, DEFAULT_PAGE_SIZE + LEAF_NODE_HEADER_SIZE, 99);
+
+        let mut page_file = inspect::PageFile::open(&db_file_path, None).unwrap();
+        let root = page_file.read_page(1).unwrap();
+        assert!(inspect::describe_page(1, &root).contains("checksum: mismatch\n"));
+        let histogram = inspect::page_type_histogram(&mut page_file).unwrap();
+        assert!(histogram.ends_with("checksum mismatches: 1\n"), "{}", histogram);
+    }
+
+    // Helper functions
+
+    fn open_table() -> Table {
+        open_table_with_config(&DbConfig::default())
+    }
+
+    fn open_table_with_config(config: &DbConfig) -> Table {
+        Table::open(&test_db_file_path(), config).unwrap()
+    }
+
+    // Every test gets its own file so tests running in parallel never share pages.
+    fn test_db_file_path() -> String {
+        static NEXT_DB_FILE_ID: AtomicUsize = AtomicUsize::new(0);
+        let file_id = NEXT_DB_FILE_ID.fetch_add(1, Ordering::SeqCst);
+        let path = std::env::temp_dir().join(format!("simpl_db_test_{}.db", file_id));
+        let path = path.to_string_lossy().into_owned();
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(format!("{}-wal", path));
+        let _ = std::fs::remove_file(format!("{}-journal", path));
+        path
+    }
+
+    fn pool_config(pool_size: usize) -> DbConfig {
+        DbConfig {
+            pool_size,
+            ..DbConfig::default()
+        }
+    }
+
+    // number of levels from the root down to the leftmost leaf
+    fn tree_depth(table: &mut Table) -> usize {
+        let mut depth = 1;
+        let mut page_num = table.root_page_num;
+        loop {
+            let node = table.pager.get_page_ref(page_num).unwrap().to_vec();
+            match get_node_type(&node, page_num).unwrap() {
+                NodeType::NodeLeaf => return depth,
+                NodeType::NodeInternal => {
+                    page_num = internal_node_child_page_num(&node, 0);
+                    depth += 1;
+                }
+            }
+        }
+    }
+
+    fn internal_node_child_page_num(node: &[u8], child_num: usize) -> usize {
+        let mut node_buffer = node.to_vec();
+        usize::from_le_bytes(internal_node_child(&mut node_buffer, child_num).try_into().unwrap())
+    }
+
+    fn open_error(db_file_path: &str) -> String {
+        match Table::open(db_file_path, &DbConfig::default()) {
+            Ok(_) => panic!("opening '{}' should fail.", db_file_path),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    // creates a fresh database file and overwrites one u32 field of its header
+    fn write_header_field(db_file_path: &str, offset: usize, value: u32) {
+        let mut table = Table::open(db_file_path, &DbConfig::default()).unwrap();
+        table.db_close();
+        let mut contents = std::fs::read(db_file_path).unwrap();
+        contents[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        std::fs::write(db_file_path, contents).unwrap();
+    }
+
+    // How many rows from `test_rows` or `insert_sequential_rows`, starting at id 1,
+    // fit in one leaf.
+    fn rows_per_leaf(page_size: usize) -> usize {
+        let mut free_space = leaf_node_space_for_cells(page_size);
+        let mut count = 0;
+        loop {
+            let row = &test_rows(count + 1..count + 2)[0];
+            match free_space.checked_sub(cell_footprint(row)) {
+                Some(left) => free_space = left,
+                None => return count as usize,
+            }
+            count += 1;
+        }
+    }
+
+    // Pages holding overflow chains, in page order.
+    fn overflow_pages(table: &mut Table) -> Vec<usize> {
+        (HEADER_PAGE_NUM + 1..table.pager.num_pages)
+            .filter(|&page_num| table.pager.get_page_ref(page_num).unwrap()[NODE_TYPE_OFFSET] == OVERFLOW_PAGE_TYPE)
+            .collect()
+    }
+
+    // How many overflow pages a row spills to, 0 when it stays in the leaf.
+    fn overflow_chain_length(row: &Row, page_size: usize) -> usize {
+        if row.serialized_size() <= leaf_node_max_local_payload(page_size) {
+            return 0;
+        }
+        (row.serialized_size() - leaf_node_spilled_local_payload(page_size)).div_ceil(overflow_page_capacity(page_size))
+    }
+
+    // The space a row takes in a leaf when it does not spill: its cell and its slot.
+    fn cell_footprint(row: &Row) -> usize {
+        LEAF_NODE_PAYLOAD_SIZE_SIZE + row.serialized_size() + LEAF_NODE_SLOT_SIZE
+    }
+
+    // The space each leaf takes for its cells and their slots, in key order.
+    fn leaf_used_spaces(table: &mut Table) -> Vec<usize> {
+        let mut page_num = table.table_start().unwrap().page_num;
+        let mut used_spaces = Vec::new();
+        loop {
+            let page = table.pager.get_page_ref(page_num).unwrap();
+            used_spaces.push(node::leaf_node_used_space(page));
+            page_num = leaf_node_next_leaf(page) as usize;
+            if page_num == 0 {
+                return used_spaces;
+            }
+        }
+    }
+
+    fn insert_sequential_rows(table: &mut Table, count: usize) -> Vec<Row> {
+        let mut inserted_rows: Vec<Row> = Vec::new();
+        for i in 0..count {
+            let row_to_insert = Row {
+                id: (i + 1) as i32,
+                username: format!("user{}", i + 1),
+                email: format!("user{}@test.com", i + 1),
+            };
+            let (execute_result, _) = insert_row_internal(table, &row_to_insert);
+            inserted_rows.push(row_to_insert);
+            match execute_result {
+                ExecuteResult::ExecuteSuccess => (),
+                _ => panic!("Error executing statement."),
+            }
+        }
+        inserted_rows
+    }
+
+    // inserts rows 1..=count in random order and returns them sorted by id
+    fn insert_shuffled_rows(table: &mut Table, count: usize) -> Vec<Row> {
+        let mut ids: Vec<usize> = (1..=count).collect();
+        ids.shuffle(&mut rand::thread_rng());
+        let mut inserted_rows: Vec<Row> = Vec::new();
+        for id in ids {
+            let row_to_insert = Row {
+                id: id as i32,
+                username: format!("user{}", id),
+                email: format!("user{}@test.com", id),
+            };
+            let (execute_result, _) = insert_row_internal(table, &row_to_insert);
+            inserted_rows.push(row_to_insert);
+            match execute_result {
+                ExecuteResult::ExecuteSuccess => (),
+                _ => panic!("Error executing statement."),
+            }
+        }
+        inserted_rows.sort_by_key(|row| row.id);
+        inserted_rows
+    }
+
+    // copies every page of the table so tests can check nothing was modified
+    fn page_snapshot(table: &mut Table) -> Vec<Vec<u8>> {
+        (0..table.pager.num_pages)
+            .map(|page_num| table.pager.get_page_ref(page_num).unwrap().to_vec())
+            .collect()
+    }
+
+    // A packed leaf has no room left for the next cell. The last two leaves may
+    // have shared their cells instead.
+    fn assert_leaves_packed(used_spaces: &[usize], space: usize, max_row: &Row) {
+        assert!(used_spaces.iter().all(|&used| used <= space));
+        let max_footprint = cell_footprint(max_row);
+        for &used in &used_spaces[..used_spaces.len().saturating_sub(2)] {
+            assert!(used + max_footprint > space, "a leaf using {} of {} bytes is not packed", used, space);
+        }
+    }
+
+    fn journal_config(journal_mode: JournalMode) -> DbConfig {
+        DbConfig {
+            page_size: MIN_PAGE_SIZE,
+            journal_mode,
+            ..DbConfig::default()
+        }
+    }
+
+    fn side_file_exists(db_file_path: &str, suffix: &str) -> bool {
+        std::path::Path::new(&format!("{}{}", db_file_path, suffix)).exists()
+    }
+
+    fn insert_rows_in_range(table: &mut Table, ids: std::ops::Range<i32>) -> Vec<Row> {
+        let mut inserted_rows = Vec::new();
+        for id in ids {
+            let row = Row {
+                id,
+                username: format!("user{}", id),
+                email: format!("user{}@test.com", id),
+            };
+            match insert_row_internal(table, &row).0 {
+                ExecuteResult::ExecuteSuccess => inserted_rows.push(row),
+                _ => panic!("Error executing statement."),
+            }
+        }
+        inserted_rows
+    }
+
+    // inserts straight into the tree, skipping the commit that follows every statement
+    fn insert_uncommitted_rows(table: &mut Table, ids: std::ops::Range<i32>) {
+        for id in ids {
+            let row = Row {
+                id,
+                username: format!("user{}", id),
+                email: format!("user{}@test.com", id),
+            };
+            let mut cursor = table.table_find(id as u32).unwrap();
+            node::leaf_node_insert(&mut cursor, id as u32, &row).unwrap();
+        }
+    }
+
+    fn wal_file_length(db_file_path: &str) -> usize {
+        std::fs::metadata(format!("{}-wal", db_file_path)).unwrap().len() as usize
+    }
+
+    fn delete_row(table: &mut Table, id: u32) -> ExecuteResult {
+        let statement = Statement {
+            row_to_insert: None,
+            statement_type: StatementType::StatementDelete(id),
+        };
+        executor::execute_statement(&statement, table)
+    }
+
+    fn update_row(table: &mut Table, id: u32, username: Option<&str>, email: Option<&str>) -> ExecuteResult {
+        let statement = Statement {
+            row_to_insert: None,
+            statement_type: StatementType::StatementUpdate(
+                id,
+                RowUpdate {
+                    username: username.map(str::to_string),
+                    email: email.map(str::to_string),
+                },
+            ),
+        };
+        executor::execute_statement(&statement, table)
+    }
+
+    fn execute_transaction_statement(table: &mut Table, statement_type: StatementType) -> ExecuteResult {
+        let statement = Statement {
+            row_to_insert: None,
+            statement_type,
+        };
+        executor::execute_statement(&statement, table)
+    }
+
+    fn test_rows(ids: std::ops::Range<i32>) -> Vec<Row> {
+        ids.map(|id| Row {
+            id,
+            username: format!("user{}", id),
+            email: format!("user{}@test.com", id),
+        })
+        .collect()
+    }
+
+    fn scan_ids(table: &mut Table, range: &KeyRange, order: ScanOrder) -> Vec<u32> {
+        let mut ids = Vec::new();
+        executor::scan_range(table, range, order, |row| ids.push(row.id as u32)).unwrap();
+        ids
+    }
+
+    fn assert_tree_is_valid(table: &mut Table) {
+        let violations = check::check_table(table);
+        assert!(violations.is_empty(), "integrity check failed: {:?}", violations);
+    }
+
+    fn assert_violations(table: &mut Table, expected: &[&str]) {
+        let violations: Vec<String> = check::check_table(table).iter().map(|v| v.to_string()).collect();
+        assert_eq!(violations, expected);
     }
 
     fn compare_data(table: &mut Table, inserted_rows: Vec<Row>) {
-        let mut cursor = table.table_start();
-        for i in 0..inserted_rows.len() {
-            let row = Row::deserialize_row(cursor.cursor_value());
-            assert_eq!(row, inserted_rows[i], "row should match inserted row.");
-            cursor.advance();
+        let mut cursor = table.table_start().unwrap();
+        for inserted_row in &inserted_rows {
+            let row = Row::deserialize_row(&cursor.cursor_value().unwrap());
+            assert_eq!(&row, inserted_row, "row should match inserted row.");
+            cursor.advance().unwrap();
         }
+        assert!(cursor.end_of_table, "table should not hold more rows than inserted.");
     }
 
     fn insert_row(table: &mut Table) -> (ExecuteResult, Row) {
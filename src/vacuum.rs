@@ -0,0 +1,72 @@
+use crate::bulk_load::{build_tree, check_fill_percent};
+use crate::config::DbConfig;
+use crate::enums::{DbError, Synchronous};
+use crate::overflow::leaf_cell;
+use crate::pager::sync_directory;
+use crate::table::Table;
+
+/// Rebuilds the table into a new file with no free pages and its leaves filled
+/// to `fill_percent` of their capacity, then renames it over the original file.
+/// Returns the number of bytes the file shrank by.
+pub fn vacuum(table: &mut Table, fill_percent: usize) -> Result<usize, DbError> {
+    check_fill_percent(fill_percent)?;
+    if table.pager.in_transaction() {
+        return Err(DbError::TransactionOpen);
+    }
+    let page_size = table.pager.page_size;
+    let old_file_size = table.pager.num_pages * page_size;
+    let payloads = table_payloads(table)?;
+
+    let file_name = table.file_name.clone();
+    let vacuum_file_name = format!("{}-vacuum", file_name);
+    // a leftover from an interrupted vacuum is simply rebuilt
+    remove_file_if_exists(&vacuum_file_name)?;
+    let config = DbConfig {
+        page_size,
+        ..table.config.clone()
+    };
+    let mut new_table = Table::open(&vacuum_file_name, &config)?;
+    // overflow chains are written anew, the old cells point at pages of the old file
+    let cells = payloads
+        .iter()
+        .map(|payload| leaf_cell(&mut new_table.pager, payload))
+        .collect::<Result<Vec<Vec<u8>>, DbError>>()?;
+    build_tree(&mut new_table, &cells, fill_percent)?;
+    new_table.db_close();
+    let new_file_size = new_table.pager.num_pages * page_size;
+    drop(new_table);
+    // the new file was checkpointed on close, its log is empty
+    remove_file_if_exists(&format!("{}-wal", vacuum_file_name))?;
+
+    // closing only syncs what the journal and synchronous settings ask for, the
+    // new file replaces the only other copy of the table so it must be on disk
+    std::fs::File::open(&vacuum_file_name)?.sync_all()?;
+
+    table.db_close();
+    std::fs::rename(&vacuum_file_name, &file_name)?;
+    if table.config.synchronous != Synchronous::Off {
+        sync_directory(&file_name)?;
+    }
+    let config = table.config.clone();
+    *table = Table::open(&file_name, &config)?;
+    Ok(old_file_size.saturating_sub(new_file_size))
+}
+
+// Copies out every serialized row in key order. The whole table is held in
+// memory while the new file is built.
+fn table_payloads(table: &mut Table) -> Result<Vec<Vec<u8>>, DbError> {
+    let mut payloads = Vec::new();
+    let mut cursor = table.table_start()?;
+    while !cursor.end_of_table {
+        payloads.push(cursor.cursor_value()?);
+        cursor.advance()?;
+    }
+    Ok(payloads)
+}
+
+fn remove_file_if_exists(file_name: &str) -> std::io::Result<()> {
+    match std::fs::remove_file(file_name) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use crate::checksum::{crc32, crc32_update};
+use crate::constants::*;
+use crate::enums::{DbError, Synchronous};
+use crate::header::{read_u32, validate_page_size, write_u32};
+
+/// Write-ahead log kept next to the database file.
+///
+/// Changed pages are appended to the log as frames and a transaction is durable
+/// once the commit record after its frames has been synced. The database file
+/// itself only changes when the log is checkpointed, so a crash can never leave
+/// it half written. Each record's checksum continues from the one before it,
+/// recovery stops at the first record that does not match and drops everything
+/// after the last commit record.
+pub struct Wal {
+    file: File,
+    // unknown until the log has a header
+    page_size: Option<usize>,
+    // offset of the newest frame of each page in the log
+    frames: HashMap<usize, u64>,
+    // pages framed since the last commit record with the offset each had before,
+    // so the frames can be dropped again if no commit record follows
+    uncommitted_frames: Vec<(usize, Option<u64>)>,
+    committed_page_count: usize,
+    // where the next record goes and where the last commit record ends
+    end_offset: u64,
+    committed_offset: u64,
+    last_checksum: u32,
+    committed_checksum: u32,
+    num_frames: usize,
+    committed_num_frames: usize,
+}
+
+impl Wal {
+    /// Opens the log, keeping every committed transaction in it.
+    pub fn open(file_name: &str) -> Result<Self, DbError> {
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(file_name)?;
+        let mut header = [0; WAL_HEADER_SIZE];
+        let has_header = file.read_exact(&mut header).is_ok();
+        let mut wal = Wal {
+            file,
+            page_size: None,
+            frames: HashMap::new(),
+            uncommitted_frames: Vec::new(),
+            committed_page_count: 0,
+            end_offset: 0,
+            committed_offset: 0,
+            last_checksum: 0,
+            committed_checksum: 0,
+            num_frames: 0,
+            committed_num_frames: 0,
+        };
+        // a log without a full header was never written to
+        if !has_header {
+            return Ok(wal);
+        }
+        if &header[WAL_MAGIC_OFFSET..WAL_MAGIC_OFFSET + WAL_MAGIC_SIZE] != WAL_MAGIC {
+            return Err(DbError::InvalidFile(
+                "not a simpl_db write-ahead log (bad magic number)".to_string(),
+            ));
+        }
+        let version = read_u32(&header, WAL_VERSION_OFFSET);
+        if version != WAL_FORMAT_VERSION {
+            return Err(DbError::InvalidFile(format!(
+                "unsupported write-ahead log version {} (expected {})",
+                version, WAL_FORMAT_VERSION
+            )));
+        }
+        let page_size = read_u32(&header, WAL_PAGE_SIZE_OFFSET) as usize;
+        validate_page_size(page_size)?;
+        wal.page_size = Some(page_size);
+        wal.end_offset = WAL_HEADER_SIZE as u64;
+        wal.last_checksum = crc32(&header);
+        wal.recover()?;
+        Ok(wal)
+    }
+
+    // Reads records up to the first one that is torn or does not match its
+    // checksum, then cuts the log back to the last commit record.
+    fn recover(&mut self) -> Result<(), DbError> {
+        self.mark_committed();
+        let page_size = self.page_size.unwrap();
+        let mut record = vec![0; WAL_RECORD_HEADER_SIZE + page_size];
+        loop {
+            self.file.seek(SeekFrom::Start(self.end_offset))?;
+            if self.file.read_exact(&mut record[..WAL_RECORD_HEADER_SIZE]).is_err() {
+                break;
+            }
+            let page_num = read_u32(&record, WAL_RECORD_PAGE_NUM_OFFSET);
+            let record_size = if page_num == WAL_COMMIT_RECORD {
+                WAL_RECORD_HEADER_SIZE
+            } else {
+                if self.file.read_exact(&mut record[WAL_RECORD_HEADER_SIZE..]).is_err() {
+                    break;
+                }
+                WAL_RECORD_HEADER_SIZE + page_size
+            };
+            let checksum = record_checksum(self.last_checksum, &record[..record_size]);
+            if checksum != read_u32(&record, WAL_RECORD_CHECKSUM_OFFSET) {
+                break;
+            }
+            if page_num != WAL_COMMIT_RECORD {
+                self.add_frame(page_num as usize, self.end_offset);
+            }
+            self.end_offset += record_size as u64;
+            self.last_checksum = checksum;
+            if page_num == WAL_COMMIT_RECORD {
+                self.committed_page_count = read_u32(&record, WAL_RECORD_PAGE_COUNT_OFFSET) as usize;
+                self.mark_committed();
+            }
+        }
+        self.drop_uncommitted_frames();
+        self.file.set_len(self.committed_offset)?;
+        Ok(())
+    }
+
+    fn add_frame(&mut self, page_num: usize, offset: u64) {
+        let previous_offset = self.frames.insert(page_num, offset);
+        self.uncommitted_frames.push((page_num, previous_offset));
+        self.num_frames += 1;
+    }
+
+    // Forgets the frames after the last commit record, the next record overwrites them.
+    fn drop_uncommitted_frames(&mut self) {
+        while let Some((page_num, previous_offset)) = self.uncommitted_frames.pop() {
+            match previous_offset {
+                Some(offset) => self.frames.insert(page_num, offset),
+                None => self.frames.remove(&page_num),
+            };
+        }
+        self.end_offset = self.committed_offset;
+        self.last_checksum = self.committed_checksum;
+        self.num_frames = self.committed_num_frames;
+    }
+
+    fn mark_committed(&mut self) {
+        self.uncommitted_frames.clear();
+        self.committed_offset = self.end_offset;
+        self.committed_checksum = self.last_checksum;
+        self.committed_num_frames = self.num_frames;
+    }
+
+    /// Starts an empty log for pages of `page_size` bytes.
+    /// Only valid once everything in the log has been checkpointed.
+    pub fn reset(&mut self, page_size: usize, synchronous: Synchronous) -> std::io::Result<()> {
+        let mut header = [0; WAL_HEADER_SIZE];
+        header[WAL_MAGIC_OFFSET..WAL_MAGIC_OFFSET + WAL_MAGIC_SIZE].copy_from_slice(WAL_MAGIC);
+        write_u32(&mut header, WAL_VERSION_OFFSET, WAL_FORMAT_VERSION);
+        write_u32(&mut header, WAL_PAGE_SIZE_OFFSET, page_size as u32);
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header)?;
+        if synchronous != Synchronous::Off {
+            self.file.sync_data()?;
+        }
+
+        self.page_size = Some(page_size);
+        self.frames.clear();
+        self.end_offset = WAL_HEADER_SIZE as u64;
+        self.last_checksum = crc32(&header);
+        self.num_frames = 0;
+        self.mark_committed();
+        Ok(())
+    }
+
+    /// Fills `page` from the newest frame of `page_num`, returns false when the log has none.
+    pub fn read_page(&mut self, page_num: usize, page: &mut [u8]) -> bool {
+        let offset = match self.frames.get(&page_num) {
+            Some(&offset) => offset,
+            None => return false,
+        };
+        self.file
+            .seek(SeekFrom::Start(offset + WAL_RECORD_HEADER_SIZE as u64))
+            .unwrap_or_else(|e| {
+                eprintln!("Error seeking in write-ahead log: {}", e);
+                panic!("Error seeking in write-ahead log.");
+            });
+        self.file.read_exact(page).unwrap_or_else(|e| {
+            eprintln!("Error reading write-ahead log: {}", e);
+            panic!("Error reading write-ahead log.");
+        });
+        true
+    }
+
+    pub fn append_page(&mut self, page_num: usize, page: &[u8]) {
+        let offset = self.end_offset;
+        self.append_record(page_num as u32, 0, page);
+        self.add_frame(page_num, offset);
+    }
+
+    /// Appends a commit record. Only at `Synchronous::Full` does it wait for
+    /// the log to reach the disk, below that the checkpoint syncs it.
+    pub fn commit(&mut self, page_count: usize, synchronous: Synchronous) {
+        self.append_record(WAL_COMMIT_RECORD, page_count as u32, &[]);
+        if synchronous == Synchronous::Full {
+            self.file.sync_data().unwrap_or_else(|e| {
+                eprintln!("Error syncing write-ahead log: {}", e);
+                panic!("Error syncing write-ahead log.");
+            });
+        }
+        self.committed_page_count = page_count;
+        self.mark_committed();
+    }
+
+    fn append_record(&mut self, page_num: u32, page_count: u32, body: &[u8]) {
+        let mut record = vec![0; WAL_RECORD_HEADER_SIZE + body.len()];
+        write_u32(&mut record, WAL_RECORD_PAGE_NUM_OFFSET, page_num);
+        write_u32(&mut record, WAL_RECORD_PAGE_COUNT_OFFSET, page_count);
+        record[WAL_RECORD_HEADER_SIZE..].copy_from_slice(body);
+        let checksum = record_checksum(self.last_checksum, &record);
+        write_u32(&mut record, WAL_RECORD_CHECKSUM_OFFSET, checksum);
+        self.file
+            .seek(SeekFrom::Start(self.end_offset))
+            .unwrap_or_else(|e| {
+                eprintln!("Error seeking in write-ahead log: {}", e);
+                panic!("Error seeking in write-ahead log.");
+            });
+        self.file.write_all(&record).unwrap_or_else(|e| {
+            eprintln!("Error writing write-ahead log: {}", e);
+            panic!("Error writing write-ahead log.");
+        });
+        self.end_offset += record.len() as u64;
+        self.last_checksum = checksum;
+    }
+
+    /// Drops the frames written since the last commit record.
+    pub fn rollback(&mut self) {
+        self.drop_uncommitted_frames();
+        self.file.set_len(self.committed_offset).unwrap_or_else(|e| {
+            eprintln!("Error truncating write-ahead log: {}", e);
+            panic!("Error truncating write-ahead log.");
+        });
+    }
+
+    /// True while frames written since the last commit record are in the log.
+    pub fn has_uncommitted_frames(&self) -> bool {
+        self.end_offset != self.committed_offset
+    }
+
+    /// Number of frames in the log since it was last checkpointed.
+    pub fn num_frames(&self) -> usize {
+        self.num_frames
+    }
+
+    /// Copies the newest committed frame of every page into the database file,
+    /// syncs it and empties the log.
+    pub fn checkpoint(&mut self, db_file: &mut File, synchronous: Synchronous) -> std::io::Result<()> {
+        if self.has_uncommitted_frames() {
+            eprintln!("Tried to checkpoint with uncommitted frames in the write-ahead log.");
+            panic!("Tried to checkpoint with uncommitted frames in the write-ahead log.");
+        }
+        let page_size = match self.page_size {
+            Some(page_size) => page_size,
+            None => return Ok(()),
+        };
+        if !self.frames.is_empty() {
+            // the frames must be on disk before the file starts to change,
+            // otherwise a crash could leave it half written with no log to redo it
+            if synchronous != Synchronous::Off {
+                self.file.sync_data()?;
+            }
+            let mut frames: Vec<(usize, u64)> =
+                self.frames.iter().map(|(&page_num, &offset)| (page_num, offset)).collect();
+            frames.sort_unstable();
+            let mut page = vec![0; page_size];
+            for (page_num, offset) in frames {
+                // a page past the end was dropped by a rollback to a savepoint
+                if page_num >= self.committed_page_count {
+                    continue;
+                }
+                self.file.seek(SeekFrom::Start(offset + WAL_RECORD_HEADER_SIZE as u64))?;
+                self.file.read_exact(&mut page)?;
+                db_file.seek(SeekFrom::Start((page_num * page_size) as u64))?;
+                db_file.write_all(&page)?;
+            }
+            let db_file_length = (self.committed_page_count * page_size) as u64;
+            if db_file.metadata()?.len() != db_file_length {
+                db_file.set_len(db_file_length)?;
+            }
+            if synchronous != Synchronous::Off {
+                db_file.sync_all()?;
+            }
+        }
+        self.reset(page_size, synchronous)
+    }
+}
+
+// Checksum of a record with its checksum field left out, continuing from the previous record.
+fn record_checksum(previous_checksum: u32, record: &[u8]) -> u32 {
+    let crc = crc32_update(previous_checksum, &record[..WAL_RECORD_CHECKSUM_OFFSET]);
+    crc32_update(crc, &record[WAL_RECORD_HEADER_SIZE..])
+}